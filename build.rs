@@ -0,0 +1,38 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use pkg_config::Config;
+
+// Versions of libblkid that introduced functions that are only compiled in if
+// the installed library supports them.
+static SUPPORTED_VERSIONS: &[(u32, u32)] = &[(2, 36), (2, 37), (2, 39)];
+
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut split = version.split('.').map(|part| {
+        part.chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse::<u32>()
+    });
+    match (split.next(), split.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => Some((major, minor)),
+        _ => None,
+    }
+}
+
+fn main() {
+    let libblkid = Config::new()
+        .cargo_metadata(false)
+        .probe("blkid")
+        .expect("Failed to find libblkid?");
+    let version = parse_version(&libblkid.version).expect("Could not parse libblkid version");
+
+    for (major, minor) in SUPPORTED_VERSIONS {
+        let cfg = format!("blkid{major}{minor}supported");
+        println!("cargo::rustc-check-cfg=cfg({cfg})");
+        if version >= (*major, *minor) {
+            println!("cargo::rustc-cfg={cfg}");
+        }
+    }
+}
@@ -1,5 +1,5 @@
-/// Checks whether a devices is in use by either a set of partitions or a partition
-/// type indicated by the superblock.
+/// Checks whether a devices is in use by either a partition table or a
+/// signature indicated by the superblock.
 use std::{
     env,
     error::Error,
@@ -7,7 +7,7 @@ use std::{
     path::Path,
 };
 
-use libblkid_rs::BlkidProbe;
+use libblkid_rs::{Ownership, OwnershipPolicy};
 
 #[derive(Debug)]
 struct ExampleError(String);
@@ -30,30 +30,29 @@ impl Display for ExampleError {
 impl Error for ExampleError {}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let path = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
         .ok_or_else(|| ExampleError::new("Path of device to check required as argument"))?;
 
-    let mut probe = BlkidProbe::new_from_filename(Path::new(&path))?;
-    probe.enable_superblocks(true)?;
-    probe.enable_partitions(true)?;
-    probe.do_safeprobe()?;
-
-    let partitions = probe
-        .get_partitions()
-        .and_then(|mut list| list.number_of_partitions());
-    let detected_use = probe.lookup_value("TYPE");
-
-    if partitions.as_ref().map(|num| *num > 0).unwrap_or(false) || detected_use.is_ok() {
-        println!("In use");
-        if let Ok(num) = partitions {
-            println!("{num} partitions found on block device");
+    // Any further arguments are signature types that do not claim the device.
+    let policy = OwnershipPolicy {
+        ignored_types: args.collect(),
+        ..Default::default()
+    };
+
+    match policy.check(Path::new(&path))? {
+        Ownership::Free => println!("Free"),
+        Ownership::Ambiguous(report) => {
+            println!("Ambiguous signatures found on block device");
+            print!("{report}");
         }
-        if let Ok(ty) = detected_use {
-            println!("Device determined to be of type {ty}");
+        Ownership::Owned(owners) => {
+            println!("In use");
+            for owner in owners {
+                println!("Device owned by {owner:?}");
+            }
         }
-    } else {
-        println!("Free");
     }
 
     Ok(())
@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::ops::Range;
+
+use crate::{
+    Result,
+    err::BlkidErr,
+    partition::BlkidPartition,
+    topology::TopologyInfo,
+    utils::{BlkidBytes, BlkidSectors},
+};
+
+// Partitions are aligned to at least 1 MiB, as fdisk and parted do, so that they
+// are aligned on devices that do not report their topology.
+const MIN_GRAIN: libc::c_ulong = 1024 * 1024;
+
+fn gcd(mut a: libc::c_ulong, mut b: libc::c_ulong) -> libc::c_ulong {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Partition alignment requirements of a device.
+///
+/// A sector is aligned if its byte offset is a multiple of the grain once the
+/// alignment offset has been subtracted. All results are in units of the
+/// logical sector size of the device while inputs may use any sector size,
+/// such as the 512-byte sectors used by `BlkidPartition`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Alignment {
+    grain: u64,
+    offset: u64,
+    sector_size: libc::c_uint,
+}
+
+impl Alignment {
+    /// Create an alignment with an explicit grain and alignment offset. Both
+    /// must be multiples of `sector_size` and the grain must not be zero.
+    pub fn new(grain: BlkidBytes, offset: BlkidBytes, sector_size: libc::c_uint) -> Result<Self> {
+        let grain = u64::try_from(*grain.as_ref()).map_err(|_| BlkidErr::InvalidConv)?;
+        let offset = u64::try_from(*offset.as_ref()).map_err(|_| BlkidErr::InvalidConv)?;
+        let size = u64::from(sector_size);
+        if grain == 0 || size == 0 || grain % size != 0 || offset % size != 0 {
+            return Err(BlkidErr::Other(format!(
+                "Alignment grain {grain} and offset {offset} must be multiples of the sector size {sector_size} and the grain must not be 0"
+            )));
+        }
+        Ok(Alignment {
+            grain,
+            offset: offset % grain,
+            sector_size,
+        })
+    }
+
+    /// Compute the alignment from the topology of a device.
+    ///
+    /// The grain is the least common multiple of 1 MiB and the optimal IO size,
+    /// or the minimum IO size if the optimal IO size is not reported or is not
+    /// a multiple of the minimum IO size. The alignment offset reported by the
+    /// device is respected.
+    pub fn from_topology(topology: &TopologyInfo) -> Result<Self> {
+        let sector_size = libc::c_uint::try_from(topology.logical_sector_size)
+            .map_err(|_| BlkidErr::InvalidConv)?;
+        let minimum_io = topology.minimum_io_size;
+        let optimal_io = topology.optimal_io_size;
+        let io_size =
+            if optimal_io != 0 && (minimum_io == 0 || optimal_io.is_multiple_of(minimum_io)) {
+                optimal_io
+            } else {
+                minimum_io
+            };
+        let grain = if io_size == 0 {
+            MIN_GRAIN
+        } else {
+            MIN_GRAIN / gcd(MIN_GRAIN, io_size) * io_size
+        };
+        Alignment::new(
+            BlkidBytes::new(
+                libblkid_rs_sys::blkid_loff_t::try_from(grain)
+                    .map_err(|_| BlkidErr::InvalidConv)?,
+            ),
+            BlkidBytes::new(
+                libblkid_rs_sys::blkid_loff_t::try_from(topology.alignment_offset)
+                    .map_err(|_| BlkidErr::InvalidConv)?,
+            ),
+            sector_size,
+        )
+    }
+
+    /// Get the alignment grain.
+    pub fn grain(&self) -> BlkidBytes {
+        BlkidBytes::new(self.grain as libblkid_rs_sys::blkid_loff_t)
+    }
+
+    /// Get the alignment offset.
+    pub fn offset(&self) -> BlkidBytes {
+        BlkidBytes::new(self.offset as libblkid_rs_sys::blkid_loff_t)
+    }
+
+    /// Get the logical sector size used for results.
+    pub fn sector_size(&self) -> libc::c_uint {
+        self.sector_size
+    }
+
+    fn to_bytes(sectors: BlkidSectors) -> Option<u64> {
+        u64::try_from(*sectors.checked_bytes()?.as_ref()).ok()
+    }
+
+    fn sectors_from_bytes(&self, bytes: u64) -> Option<BlkidSectors> {
+        libblkid_rs_sys::blkid_loff_t::try_from(bytes / u64::from(self.sector_size))
+            .ok()
+            .map(|num| BlkidSectors::new(num, self.sector_size))
+    }
+
+    /// Check whether the given sector is aligned.
+    pub fn is_aligned(&self, sector: BlkidSectors) -> bool {
+        Self::to_bytes(sector)
+            .map(|bytes| bytes % self.grain == self.offset)
+            .unwrap_or(false)
+    }
+
+    /// Check whether the start of the given partition is aligned.
+    pub fn is_partition_aligned(&self, partition: &BlkidPartition<'_>) -> bool {
+        self.is_aligned(partition.get_start())
+    }
+
+    /// Get the first aligned sector at or after the given sector or `None` if
+    /// there is none that can be represented.
+    pub fn align_up(&self, sector: BlkidSectors) -> Option<BlkidSectors> {
+        let bytes = Self::to_bytes(sector)?;
+        let aligned = if bytes <= self.offset {
+            self.offset
+        } else {
+            bytes.checked_add((self.grain - (bytes - self.offset) % self.grain) % self.grain)?
+        };
+        self.sectors_from_bytes(aligned)
+    }
+
+    /// Get the last aligned sector at or before the given sector or `None` if
+    /// there is none.
+    pub fn align_down(&self, sector: BlkidSectors) -> Option<BlkidSectors> {
+        let bytes = Self::to_bytes(sector)?;
+        if bytes < self.offset {
+            return None;
+        }
+        self.sectors_from_bytes(bytes - (bytes - self.offset) % self.grain)
+    }
+
+    /// Compute the aligned placement of a partition of `size` within the free
+    /// region `free`, where the end of `free` is exclusive. Returns the range
+    /// of sectors of the partition with an exclusive end or `None` if it does
+    /// not fit.
+    ///
+    /// The start is the first aligned sector in the region. If the requested
+    /// size is at least one grain, the end is rounded down to an aligned
+    /// sector so that a following partition can also be aligned.
+    pub fn aligned_range(
+        &self,
+        free: Range<BlkidSectors>,
+        size: BlkidSectors,
+    ) -> Option<Range<BlkidSectors>> {
+        let start = Self::to_bytes(self.align_up(free.start)?)?;
+        let size = Self::to_bytes(size)?;
+        let free_end = Self::to_bytes(free.end)?;
+        if size == 0 {
+            return None;
+        }
+        let mut end = start.checked_add(size)?;
+        if size >= self.grain {
+            end -= (end - self.offset) % self.grain;
+        } else {
+            end = end.div_ceil(u64::from(self.sector_size)) * u64::from(self.sector_size);
+        }
+        if end > free_end {
+            return None;
+        }
+        Some(self.sectors_from_bytes(start)?..self.sectors_from_bytes(end)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn topology(alignment_offset: u64, minimum_io: u64, optimal_io: u64) -> TopologyInfo {
+        TopologyInfo {
+            alignment_offset: alignment_offset as libc::c_ulong,
+            minimum_io_size: minimum_io as libc::c_ulong,
+            optimal_io_size: optimal_io as libc::c_ulong,
+            logical_sector_size: 512,
+            physical_sector_size: 4096,
+            dax: None,
+            diskseq: None,
+        }
+    }
+
+    #[test]
+    fn test_grain_from_topology() {
+        let plain = Alignment::from_topology(&topology(0, 512, 0)).unwrap();
+        assert_eq!(*plain.grain().as_ref(), 1024 * 1024);
+        let raid = Alignment::from_topology(&topology(0, 65536, 196608)).unwrap();
+        assert_eq!(*raid.grain().as_ref(), 3 * 1024 * 1024);
+        let large = Alignment::from_topology(&topology(0, 4096, 4 * 1024 * 1024)).unwrap();
+        assert_eq!(*large.grain().as_ref(), 4 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_align_with_offset() {
+        let alignment = Alignment::from_topology(&topology(3584, 4096, 0)).unwrap();
+        assert!(alignment.is_aligned(BlkidSectors::new(7, 512)));
+        assert!(!alignment.is_aligned(BlkidSectors::new(2048, 512)));
+        assert_eq!(
+            alignment.align_up(BlkidSectors::new(63, 512)),
+            Some(BlkidSectors::new(2055, 512))
+        );
+        assert_eq!(
+            alignment.align_down(BlkidSectors::new(4000, 512)),
+            Some(BlkidSectors::new(2055, 512))
+        );
+        assert_eq!(alignment.align_down(BlkidSectors::new(6, 512)), None);
+    }
+
+    #[test]
+    fn test_aligned_range() {
+        let alignment = Alignment::from_topology(&topology(0, 512, 0)).unwrap();
+        let range = alignment
+            .aligned_range(
+                BlkidSectors::new(34, 512)..BlkidSectors::new(1_000_000, 512),
+                BlkidSectors::new(5000, 512),
+            )
+            .unwrap();
+        assert_eq!(range.start, BlkidSectors::new(2048, 512));
+        assert_eq!(range.end, BlkidSectors::new(6144, 512));
+        assert!(
+            alignment
+                .aligned_range(
+                    BlkidSectors::new(34, 512)..BlkidSectors::new(4000, 512),
+                    BlkidSectors::new(4096, 512),
+                )
+                .is_none()
+        );
+    }
+}
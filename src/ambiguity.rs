@@ -0,0 +1,254 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::{self, Display};
+
+use crate::{
+    Result,
+    consts::{
+        BlkidFltr, BlkidProbeRet, BlkidSafeprobeRet, BlkidSublks, BlkidSublksFlags, BlkidUsageFlag,
+    },
+    probe::BlkidProbe,
+};
+
+/// A superblock detected while diagnosing an ambiguous probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Superblock type such as `xfs`
+    pub sig_type: String,
+    /// Usage of the superblock
+    pub usage: Option<BlkidUsageFlag>,
+    /// Offset of the superblock magic in bytes
+    pub offset: Option<u64>,
+    /// Superblock version
+    pub version: Option<String>,
+    /// UUID of the superblock
+    pub uuid: Option<String>,
+    /// Label of the superblock
+    pub label: Option<String>,
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.sig_type)?;
+        match self.usage {
+            Some(BlkidUsageFlag::Filesystem) => write!(f, ": filesystem")?,
+            Some(BlkidUsageFlag::Raid) => write!(f, ": raid")?,
+            Some(BlkidUsageFlag::Crypto) => write!(f, ": crypto")?,
+            Some(BlkidUsageFlag::Other) => write!(f, ": other")?,
+            None => (),
+        }
+        if let Some(offset) = self.offset {
+            write!(f, ", magic at offset {offset:#x}")?;
+        }
+        if let Some(ref uuid) = self.uuid {
+            write!(f, ", UUID {uuid}")?;
+        }
+        Ok(())
+    }
+}
+
+/// All superblocks detected on a device and the pairs of them that
+/// `do_safeprobe` reports as ambiguous.
+///
+/// libblkid tolerates some combinations of superblocks and stops probing at
+/// the first RAID or crypto superblock, so a report with several candidates
+/// does not necessarily contain any conflicts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AmbiguityReport {
+    /// Every superblock detected, in probing order
+    pub candidates: Vec<Candidate>,
+    /// Pairs of indices into `candidates` of superblocks that `do_safeprobe`
+    /// reports as ambiguous when they are the only types probed for
+    pub conflicting_pairs: Vec<(usize, usize)>,
+}
+
+impl AmbiguityReport {
+    /// Whether any detected superblocks conflict with each other.
+    pub fn is_ambiguous(&self) -> bool {
+        !self.conflicting_pairs.is_empty()
+    }
+
+    /// Get the pairs of detected superblocks that conflict with each other.
+    pub fn conflicts(&self) -> Vec<(&Candidate, &Candidate)> {
+        self.conflicting_pairs
+            .iter()
+            .map(|&(a, b)| (&self.candidates[a], &self.candidates[b]))
+            .collect()
+    }
+}
+
+impl Display for AmbiguityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.conflicting_pairs.is_empty() {
+            let names = self
+                .candidates
+                .iter()
+                .map(|c| c.sig_type.as_str())
+                .collect::<Vec<_>>();
+            match names.as_slice() {
+                [] => writeln!(f, "no superblocks detected")?,
+                [name] => writeln!(f, "only {name} detected")?,
+                names => writeln!(f, "{} detected without conflicts", names.join(", "))?,
+            }
+        }
+        for (a, b) in self.conflicts() {
+            writeln!(f, "both {} and {} detected", a.sig_type, b.sig_type)?;
+        }
+        for candidate in &self.candidates {
+            writeln!(f, "  {candidate}")?;
+        }
+        Ok(())
+    }
+}
+
+impl BlkidProbe {
+    /// Find every superblock on the device, including those that
+    /// `do_safeprobe` would report as ambiguous, and determine which of them
+    /// conflict. Superblock types in `ignored_types` are not probed for.
+    ///
+    /// The superblocks are probed for with a separate probe on the same
+    /// device so the configuration and state of this probe are left
+    /// unchanged and its filters are not applied.
+    pub fn get_superblock_candidates(&self, ignored_types: &[&str]) -> Result<AmbiguityReport> {
+        let mut probe = BlkidProbe::new()?;
+        probe.set_device(self.get_fd()?, self.get_offset(), self.get_size())?;
+        probe.set_sector_size(self.get_sector_size())?;
+        probe.enable_superblocks(true)?;
+        probe.set_superblock_flags(BlkidSublksFlags::new(vec![
+            BlkidSublks::Type,
+            BlkidSublks::Usage,
+            BlkidSublks::Uuid,
+            BlkidSublks::Label,
+            BlkidSublks::Version,
+            BlkidSublks::Magic,
+        ]))?;
+        probe.filter_superblock_type(BlkidFltr::Notin, ignored_types)?;
+
+        let mut report = AmbiguityReport::default();
+        while probe.do_probe()? == BlkidProbeRet::Success {
+            let result = probe.get_result()?;
+            let uuid = result.uuid_string();
+            let sig_type = match result.fs_type {
+                Some(sig_type) => sig_type,
+                None => continue,
+            };
+            report.candidates.push(Candidate {
+                sig_type,
+                usage: result.usage,
+                offset: result.sbmagic_offset,
+                version: result.version,
+                uuid,
+                label: result.label,
+            });
+        }
+
+        // Whether two superblocks conflict depends on flags internal to
+        // libblkid so ask do_safeprobe about each pair.
+        for a in 0..report.candidates.len() {
+            for b in a + 1..report.candidates.len() {
+                let pair = [
+                    report.candidates[a].sig_type.as_str(),
+                    report.candidates[b].sig_type.as_str(),
+                ];
+                if pair[0] == pair[1] {
+                    continue;
+                }
+                probe.filter_superblock_type(BlkidFltr::Onlyin, &pair)?;
+                probe.reset();
+                if probe.do_safeprobe()? == BlkidSafeprobeRet::Ambiguous {
+                    report.conflicting_pairs.push((a, b));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Same as `do_safeprobe` but if the result is ambiguous, also return a
+    /// report of all superblocks found and which of them conflict. The report
+    /// is gathered with `get_superblock_candidates` so the filters of this
+    /// probe are not applied to it.
+    pub fn do_safeprobe_diagnostic(
+        &mut self,
+    ) -> Result<(BlkidSafeprobeRet, Option<AmbiguityReport>)> {
+        match self.do_safeprobe()? {
+            BlkidSafeprobeRet::Ambiguous => Ok((
+                BlkidSafeprobeRet::Ambiguous,
+                Some(self.get_superblock_candidates(&[])?),
+            )),
+            ret => Ok((ret, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::ext2_swap_image;
+
+    #[test]
+    fn test_report() {
+        let candidate = |sig_type: &str, usage, offset| Candidate {
+            sig_type: sig_type.to_string(),
+            usage: Some(usage),
+            offset: Some(offset),
+            version: None,
+            uuid: None,
+            label: None,
+        };
+        let mut report = AmbiguityReport {
+            candidates: vec![
+                candidate("xfs", BlkidUsageFlag::Filesystem, 0),
+                candidate("LVM2_member", BlkidUsageFlag::Raid, 0x218),
+            ],
+            conflicting_pairs: vec![(0, 1)],
+        };
+        assert!(report.is_ambiguous());
+        assert_eq!(report.conflicts().len(), 1);
+        assert_eq!(
+            report.to_string(),
+            "both xfs and LVM2_member detected\n  xfs: filesystem, magic at offset 0x0\n  LVM2_member: raid, magic at offset 0x218\n"
+        );
+        report.conflicting_pairs.clear();
+        assert!(!report.is_ambiguous());
+        assert!(
+            report
+                .to_string()
+                .starts_with("xfs, LVM2_member detected without conflicts\n")
+        );
+    }
+
+    #[test]
+    fn test_superblock_candidates() {
+        let mut probe = BlkidProbe::from_bytes(&ext2_swap_image()).unwrap();
+        probe
+            .filter_superblock_type(BlkidFltr::Notin, &["swap"])
+            .unwrap();
+
+        let report = probe.get_superblock_candidates(&[]).unwrap();
+        let types = report
+            .candidates
+            .iter()
+            .map(|c| c.sig_type.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec!["swap", "ext2"]);
+        assert_eq!(report.conflicting_pairs, vec![(0, 1)]);
+        assert!(
+            probe
+                .get_superblock_candidates(&["swap"])
+                .unwrap()
+                .conflicting_pairs
+                .is_empty()
+        );
+
+        // The filter of the probe is left in place.
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Success);
+        assert_eq!(probe.lookup_value("TYPE").unwrap(), "ext2");
+        probe.filter_superblock_type(BlkidFltr::Notin, &[]).unwrap();
+        let (ret, report) = probe.do_safeprobe_diagnostic().unwrap();
+        assert_eq!(ret, BlkidSafeprobeRet::Ambiguous);
+        assert!(report.unwrap().is_ambiguous());
+    }
+}
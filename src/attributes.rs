@@ -0,0 +1,175 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{Result, partition::BlkidPartition};
+
+/// GPT partition attribute bits.
+///
+/// Bits 0 to 2 are defined for all partitions by the UEFI specification. Bits
+/// 48 to 63 are specific to the partition type; the accessors for them follow
+/// the meaning defined for Microsoft basic data partitions and for Linux
+/// partitions by the Discoverable Partitions Specification.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GptAttributes(u64);
+
+impl GptAttributes {
+    /// Create attributes from the raw attribute bits.
+    pub fn new(bits: u64) -> Self {
+        GptAttributes(bits)
+    }
+
+    /// Get the raw attribute bits.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    fn bit(&self, bit: u32) -> bool {
+        self.0 & (1 << bit) != 0
+    }
+
+    /// The partition is required for the platform to function and must not be
+    /// deleted or modified.
+    pub fn required(&self) -> bool {
+        self.bit(0)
+    }
+
+    /// Firmware must not produce a block IO protocol for the partition.
+    pub fn no_block_io_protocol(&self) -> bool {
+        self.bit(1)
+    }
+
+    /// The partition may be booted by legacy BIOS firmware.
+    pub fn legacy_bios_bootable(&self) -> bool {
+        self.bit(2)
+    }
+
+    /// Get the type-specific bits 48 to 63.
+    pub fn type_specific(&self) -> u16 {
+        (self.0 >> 48) as u16
+    }
+
+    /// The filesystem should be grown to fill the partition when mounted.
+    pub fn grow_file_system(&self) -> bool {
+        self.bit(59)
+    }
+
+    /// The partition should be mounted read-only.
+    pub fn read_only(&self) -> bool {
+        self.bit(60)
+    }
+
+    /// The partition is a shadow copy of another partition.
+    pub fn shadow_copy(&self) -> bool {
+        self.bit(61)
+    }
+
+    /// The partition should be hidden from the user.
+    pub fn hidden(&self) -> bool {
+        self.bit(62)
+    }
+
+    /// The partition should not be mounted automatically.
+    pub fn no_automount(&self) -> bool {
+        self.bit(63)
+    }
+}
+
+/// DOS partition flags, which consist of the boot indicator.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DosFlags(u64);
+
+impl DosFlags {
+    /// Create flags from the raw value reported by libblkid.
+    pub fn new(bits: u64) -> Self {
+        DosFlags(bits)
+    }
+
+    /// Get the raw flags.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// The partition is marked active for booting.
+    pub fn bootable(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+}
+
+/// Partition flags decoded according to the type of the partition table.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionFlags {
+    /// Attributes of a GPT partition
+    Gpt(GptAttributes),
+    /// Flags of a DOS partition
+    Dos(DosFlags),
+    /// Flags of a partition in any other partition table
+    Other(u64),
+}
+
+impl PartitionFlags {
+    pub(crate) fn from_raw(pt_type: &str, flags: u64) -> Self {
+        match pt_type {
+            "gpt" => PartitionFlags::Gpt(GptAttributes::new(flags)),
+            "dos" => PartitionFlags::Dos(DosFlags::new(flags)),
+            _ => PartitionFlags::Other(flags),
+        }
+    }
+
+    /// Get the raw flags.
+    pub fn bits(&self) -> u64 {
+        match self {
+            PartitionFlags::Gpt(attrs) => attrs.bits(),
+            PartitionFlags::Dos(flags) => flags.bits(),
+            PartitionFlags::Other(flags) => *flags,
+        }
+    }
+
+    /// Whether the partition is a GPT partition marked as required.
+    pub fn is_required(&self) -> bool {
+        matches!(self, PartitionFlags::Gpt(attrs) if attrs.required())
+    }
+
+    /// Whether the partition is a GPT partition marked as read-only.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, PartitionFlags::Gpt(attrs) if attrs.read_only())
+    }
+}
+
+impl BlkidPartition<'_> {
+    /// Get the flags of the partition decoded according to the type of its
+    /// partition table.
+    pub fn get_partition_flags(&self) -> Result<PartitionFlags> {
+        Ok(PartitionFlags::from_raw(
+            &self.get_table()?.get_type()?,
+            self.get_flags(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_flags() {
+        let gpt = PartitionFlags::from_raw("gpt", 1 | 1 << 60);
+        assert!(gpt.is_required());
+        assert!(gpt.is_read_only());
+        assert_eq!(
+            gpt,
+            PartitionFlags::Gpt(GptAttributes::new(0x1000_0000_0000_0001))
+        );
+        if let PartitionFlags::Gpt(attrs) = gpt {
+            assert!(!attrs.legacy_bios_bootable());
+            assert_eq!(attrs.type_specific(), 0x1000);
+        }
+        let dos = PartitionFlags::from_raw("dos", 0x80);
+        assert!(!dos.is_required());
+        assert_eq!(dos, PartitionFlags::Dos(DosFlags::new(0x80)));
+        assert!(DosFlags::new(0x80).bootable());
+    }
+}
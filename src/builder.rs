@@ -0,0 +1,399 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    os::fd::{OwnedFd, RawFd},
+    path::Path,
+};
+
+use crate::{
+    Result,
+    consts::{
+        BlkidFltr, BlkidParts, BlkidPartsFlags, BlkidSublks, BlkidSublksFlags, BlkidUsageFlag,
+        BlkidUsageFlags,
+    },
+    err::BlkidErr,
+    probe::{BlkidProbe, is_known_fs_type, is_known_partition_type},
+};
+
+/// A reusable probe configuration.
+///
+/// The builder records which probing chains are enabled and how they are
+/// configured. The configuration is validated before it is applied and can be
+/// used to build any number of probes. As in libblkid, only superblock probing
+/// is enabled by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ProbeBuilder {
+    superblocks: bool,
+    superblock_flags: Option<Vec<BlkidSublks>>,
+    superblock_types: Option<(BlkidFltr, Vec<String>)>,
+    superblock_usage: Option<(BlkidFltr, Vec<BlkidUsageFlag>)>,
+    partitions: bool,
+    partition_flags: Option<Vec<BlkidParts>>,
+    partition_types: Option<(BlkidFltr, Vec<String>)>,
+    topology: bool,
+    sector_size: Option<libc::c_uint>,
+}
+
+impl Default for ProbeBuilder {
+    fn default() -> Self {
+        ProbeBuilder {
+            superblocks: true,
+            superblock_flags: None,
+            superblock_types: None,
+            superblock_usage: None,
+            partitions: false,
+            partition_flags: None,
+            partition_types: None,
+            topology: false,
+            sector_size: None,
+        }
+    }
+}
+
+impl ProbeBuilder {
+    /// Create a configuration with the default settings of libblkid.
+    pub fn new() -> Self {
+        ProbeBuilder::default()
+    }
+
+    /// Enable or disable superblock probing.
+    pub fn superblocks(mut self, enable: bool) -> Self {
+        self.superblocks = enable;
+        self
+    }
+
+    /// Set the superblock flags.
+    pub fn superblock_flags(mut self, flags: Vec<BlkidSublks>) -> Self {
+        self.superblock_flags = Some(flags);
+        self
+    }
+
+    /// Filter superblocks by type. Every name must be a type known to
+    /// libblkid.
+    pub fn filter_superblock_type(mut self, flag: BlkidFltr, names: &[&str]) -> Self {
+        self.superblock_types = Some((flag, names.iter().map(|s| s.to_string()).collect()));
+        self
+    }
+
+    /// Filter superblocks by usage.
+    pub fn filter_superblock_usage(mut self, flag: BlkidFltr, usage: Vec<BlkidUsageFlag>) -> Self {
+        self.superblock_usage = Some((flag, usage));
+        self
+    }
+
+    /// Enable or disable partition probing.
+    pub fn partitions(mut self, enable: bool) -> Self {
+        self.partitions = enable;
+        self
+    }
+
+    /// Set the partition flags.
+    pub fn partition_flags(mut self, flags: Vec<BlkidParts>) -> Self {
+        self.partition_flags = Some(flags);
+        self
+    }
+
+    /// Filter partition tables by type. Every name must be a type known to
+    /// libblkid.
+    pub fn filter_partition_types(mut self, flag: BlkidFltr, names: &[&str]) -> Self {
+        self.partition_types = Some((flag, names.iter().map(|s| s.to_string()).collect()));
+        self
+    }
+
+    /// Enable or disable topology probing.
+    pub fn topology(mut self, enable: bool) -> Self {
+        self.topology = enable;
+        self
+    }
+
+    /// Override the logical sector size of probed devices.
+    pub fn sector_size(mut self, size: libc::c_uint) -> Self {
+        self.sector_size = Some(size);
+        self
+    }
+
+    /// Check that the configuration is consistent. Flags and filters must
+    /// only be set for enabled chains, filter type names must be known to
+    /// libblkid, an `Onlyin` filter must not be empty, and the sector size
+    /// must be a power of two of at least 512 bytes. libblkid keeps a single
+    /// superblock filter so superblocks can be filtered by type or by usage
+    /// but not both.
+    pub fn validate(&self) -> Result<()> {
+        fn invalid(msg: &str) -> Result<()> {
+            Err(BlkidErr::Other(format!(
+                "Invalid probe configuration: {msg}"
+            )))
+        }
+
+        if !self.superblocks
+            && (self.superblock_flags.is_some()
+                || self.superblock_types.is_some()
+                || self.superblock_usage.is_some())
+        {
+            return invalid("superblock flags or filters set with superblock probing disabled");
+        }
+        if !self.partitions && (self.partition_flags.is_some() || self.partition_types.is_some()) {
+            return invalid("partition flags or filters set with partition probing disabled");
+        }
+        if let Some((flag, ref names)) = self.superblock_types {
+            if flag == BlkidFltr::Onlyin && names.is_empty() {
+                return invalid("empty superblock type filter would match nothing");
+            }
+            for name in names {
+                if !is_known_fs_type(name)? {
+                    return invalid(&format!("unknown superblock type {name}"));
+                }
+            }
+        }
+        if self.superblock_types.is_some() && self.superblock_usage.is_some() {
+            return invalid("superblock type and usage filters cannot be combined");
+        }
+        if let Some((flag, ref usage)) = self.superblock_usage {
+            if flag == BlkidFltr::Onlyin && usage.is_empty() {
+                return invalid("empty superblock usage filter would match nothing");
+            }
+        }
+        if let Some((flag, ref names)) = self.partition_types {
+            if flag == BlkidFltr::Onlyin && names.is_empty() {
+                return invalid("empty partition type filter would match nothing");
+            }
+            for name in names {
+                if !is_known_partition_type(name) {
+                    return invalid(&format!("unknown partition type {name}"));
+                }
+            }
+        }
+        if let Some(size) = self.sector_size {
+            if size < 512 || !size.is_power_of_two() {
+                return invalid(&format!(
+                    "sector size {size} is not a power of two of at least 512"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the configuration and apply it to an existing probe. Flags
+    /// and filters that are not configured are reset to the defaults of
+    /// libblkid so no settings of the probe are left over. A configured
+    /// sector size is lost if a device is assigned to the probe afterwards.
+    pub fn configure(&self, probe: &mut BlkidProbe) -> Result<()> {
+        self.validate()?;
+
+        if let Some(size) = self.sector_size {
+            probe.set_sector_size(size)?;
+        }
+
+        probe.enable_superblocks(self.superblocks)?;
+        probe.set_superblock_flags(BlkidSublksFlags::new(
+            self.superblock_flags
+                .clone()
+                .unwrap_or_else(|| vec![BlkidSublks::Default]),
+        ))?;
+        // An empty Notin filter lets every type through. libblkid fails to
+        // reset a filter that was never set.
+        match (&self.superblock_types, &self.superblock_usage) {
+            (Some((flag, names)), _) => probe.filter_superblock_type(
+                *flag,
+                &names.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            )?,
+            (None, Some((flag, usage))) => {
+                probe.filter_superblock_usage(*flag, BlkidUsageFlags::new(usage.clone()))?
+            }
+            (None, None) => probe.filter_superblock_type(BlkidFltr::Notin, &[])?,
+        }
+
+        probe.enable_partitions(self.partitions)?;
+        probe.set_partition_flags(BlkidPartsFlags::new(
+            self.partition_flags.clone().unwrap_or_default(),
+        ))?;
+        match self.partition_types {
+            Some((flag, ref names)) => probe.filter_partition_types(
+                flag,
+                &names.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            )?,
+            None => probe.filter_partition_types(BlkidFltr::Notin, &[])?,
+        }
+
+        probe.enable_topology(self.topology)?;
+        Ok(())
+    }
+
+    /// Build a configured probe without an associated device.
+    ///
+    /// Assigning a device resets the sector size of a probe so this fails if
+    /// a sector size is configured. Use `build_with_device` instead.
+    pub fn build(&self) -> Result<BlkidProbe> {
+        self.validate()?;
+        if self.sector_size.is_some() {
+            return Err(BlkidErr::Other(
+                "A sector size can only be configured for a probe with a device".to_string(),
+            ));
+        }
+        let mut probe = BlkidProbe::new()?;
+        self.configure(&mut probe)?;
+        Ok(probe)
+    }
+
+    /// Build a configured probe for `size` bytes starting at `offset` bytes
+    /// into the device `fd`. A `size` of `0` probes to the end of the device.
+    ///
+    /// As with `BlkidProbe::set_device`, the file descriptor must remain
+    /// open for as long as the probe uses it.
+    pub fn build_with_device(
+        &self,
+        fd: RawFd,
+        offset: libblkid_rs_sys::blkid_loff_t,
+        size: libblkid_rs_sys::blkid_loff_t,
+    ) -> Result<BlkidProbe> {
+        self.validate()?;
+        let mut probe = BlkidProbe::new()?;
+        probe.set_device(fd, offset, size)?;
+        self.configure(&mut probe)?;
+        Ok(probe)
+    }
+
+    /// Build a configured probe for the device or file at `path`.
+    pub fn build_for_path(&self, path: &Path) -> Result<BlkidProbe> {
+        self.validate()?;
+        let mut probe = BlkidProbe::new_from_filename(path)?;
+        self.configure(&mut probe)?;
+        Ok(probe)
+    }
+
+    /// Build a configured probe that owns the file descriptor `fd`.
+    pub fn build_for_fd<F>(&self, fd: F) -> Result<BlkidProbe>
+    where
+        F: Into<OwnedFd>,
+    {
+        self.validate()?;
+        let mut probe = BlkidProbe::new_from_fd(fd, 0, 0)?;
+        self.configure(&mut probe)?;
+        Ok(probe)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::os::fd::AsRawFd;
+
+    use crate::{
+        consts::BlkidSafeprobeRet,
+        test_utils::{TempDir, ext2_swap_image},
+    };
+
+    #[test]
+    fn test_validate() {
+        assert!(ProbeBuilder::new().validate().is_ok());
+        assert!(
+            ProbeBuilder::new()
+                .partitions(true)
+                .filter_superblock_type(BlkidFltr::Notin, &["swap"])
+                .filter_partition_types(BlkidFltr::Onlyin, &["gpt", "dos"])
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            ProbeBuilder::new()
+                .partition_flags(vec![BlkidParts::Magic])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ProbeBuilder::new()
+                .filter_superblock_type(BlkidFltr::Onlyin, &["not_a_filesystem"])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ProbeBuilder::new()
+                .filter_superblock_type(BlkidFltr::Notin, &["swap"])
+                .filter_superblock_usage(BlkidFltr::Notin, vec![BlkidUsageFlag::Other])
+                .validate()
+                .is_err()
+        );
+        assert!(ProbeBuilder::new().sector_size(1000).validate().is_err());
+    }
+
+    #[test]
+    fn test_build_with_sector_size() {
+        let dir = TempDir::new("builder");
+        let path = dir.path().join("disk.img");
+        std::fs::write(&path, vec![0u8; 1 << 20]).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        let builder = ProbeBuilder::new().partitions(true).sector_size(4096);
+        assert!(builder.build().is_err());
+        let probe = builder.build_with_device(file.as_raw_fd(), 0, 0).unwrap();
+        assert_eq!(probe.get_sector_size(), 4096);
+        assert_eq!(
+            builder.build_for_path(&path).unwrap().get_sector_size(),
+            4096
+        );
+        let mut probe = ProbeBuilder::new().partitions(true).build().unwrap();
+        probe.set_device(file.as_raw_fd(), 0, 0).unwrap();
+        assert_eq!(probe.get_sector_size(), 512);
+    }
+
+    #[test]
+    fn test_build_with_topology() {
+        let dir = TempDir::new("builder-topology");
+        let path = dir.path().join("disk.img");
+        std::fs::write(&path, vec![0u8; 1 << 20]).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        let builder = ProbeBuilder::new().topology(true);
+        assert!(builder.build().is_ok());
+        assert!(builder.build_with_device(file.as_raw_fd(), 0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_configure_resets_probe() {
+        let mut probe = BlkidProbe::from_bytes(&ext2_swap_image()).unwrap();
+        ProbeBuilder::new()
+            .superblock_flags(vec![BlkidSublks::Type, BlkidSublks::Magic])
+            .filter_superblock_type(BlkidFltr::Notin, &["swap"])
+            .configure(&mut probe)
+            .unwrap();
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Success);
+        assert_eq!(probe.lookup_value("TYPE").unwrap(), "ext2");
+        assert!(probe.lookup_value("SBMAGIC_OFFSET").is_ok());
+
+        // The filter is cleared when the configuration has none.
+        ProbeBuilder::new().configure(&mut probe).unwrap();
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Ambiguous);
+
+        // The flags are reset to the defaults of libblkid.
+        ProbeBuilder::new()
+            .filter_superblock_usage(BlkidFltr::Notin, vec![BlkidUsageFlag::Other])
+            .configure(&mut probe)
+            .unwrap();
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Success);
+        assert_eq!(probe.lookup_value("TYPE").unwrap(), "ext2");
+        assert!(probe.lookup_value("SBMAGIC_OFFSET").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let builder = ProbeBuilder::new()
+            .partitions(true)
+            .partition_flags(vec![BlkidParts::EntryDetails])
+            .filter_superblock_usage(BlkidFltr::Notin, vec![BlkidUsageFlag::Other]);
+        let json = serde_json::to_string(&builder).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ProbeBuilder>(&json).unwrap(),
+            builder
+        );
+        assert_eq!(
+            serde_json::from_str::<ProbeBuilder>(r#"{"topology": true}"#).unwrap(),
+            ProbeBuilder::new().topology(true)
+        );
+    }
+}
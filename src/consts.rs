@@ -75,6 +75,25 @@ flags!(
     BlkidSublks
 );
 
+consts_enum_conv!(
+    /// Flags for partition probing
+    BlkidParts <=> c_int,
+    /// Probe for GPT even if there is no protective MBR
+    ForceGpt => libblkid_rs_sys::BLKID_PARTS_FORCE_GPT as c_int,
+    /// Define `PART_ENTRY_*` values such as `PART_ENTRY_UUID` and
+    /// `PART_ENTRY_NAME` for the probed partition
+    EntryDetails => libblkid_rs_sys::BLKID_PARTS_ENTRY_DETAILS as c_int,
+    /// Read partition table magic number and define `PTMAGIC` and
+    /// `PTMAGIC_OFFSET`
+    Magic => libblkid_rs_sys::BLKID_PARTS_MAGIC as c_int
+);
+
+flags!(
+    /// Set of `BlkidParts` flags
+    BlkidPartsFlags <=> c_int,
+    BlkidParts
+);
+
 consts_enum_conv!(
     /// Constants for probing superblock type.
     BlkidFltr <=> c_int,
@@ -7,8 +7,41 @@ use std::ffi::CStr;
 use crate::err::Result;
 
 /// Device number
+///
+/// With the `serde` feature, a device number is serialized as its major and
+/// minor numbers.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "DeviceNumbers", into = "DeviceNumbers")
+)]
 pub struct BlkidDevno(libc::dev_t);
 
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DeviceNumbers {
+    major: maj_t,
+    minor: min_t,
+}
+
+#[cfg(feature = "serde")]
+impl From<BlkidDevno> for DeviceNumbers {
+    fn from(devno: BlkidDevno) -> Self {
+        DeviceNumbers {
+            major: devno.major(),
+            minor: devno.minor(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<DeviceNumbers> for BlkidDevno {
+    fn from(numbers: DeviceNumbers) -> Self {
+        BlkidDevno::from_device_numbers(numbers.major, numbers.minor)
+    }
+}
+
 #[cfg(target_os = "linux")]
 pub mod ty {
     /// Type alias for device major number
@@ -89,3 +122,24 @@ impl BlkidDevno {
         Ok((name, BlkidDevno(wholedisk_devno)))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_numbers() {
+        let devno = BlkidDevno::from_device_numbers(259, 3);
+        assert_eq!(devno.major(), 259);
+        assert_eq!(devno.minor(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let devno = BlkidDevno::from_device_numbers(259, 3);
+        let json = serde_json::to_string(&devno).unwrap();
+        assert_eq!(json, r#"{"major":259,"minor":3}"#);
+        assert_eq!(serde_json::from_str::<BlkidDevno>(&json).unwrap(), devno);
+    }
+}
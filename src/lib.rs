@@ -20,12 +20,23 @@
 //!   characters are required.
 //! * `blkid_get_dev_size` - This method takes a `&Path` in the bindings
 //!   and provides libblkid with the desired file descriptor.
+//!
+//! ### Features
+//! * `deprecated` - Bindings for methods deprecated in libblkid.
+//! * `serde` - Implementations of `Serialize` and `Deserialize` for probe
+//!   results, partition tables, topology information, device numbers, the
+//!   constants in `consts` and `ProbeBuilder`.
+//! * `static` - Link libblkid statically.
 
 #![deny(missing_docs)]
 
 #[macro_use]
 mod macros;
 
+mod alignment;
+mod ambiguity;
+mod attributes;
+mod builder;
 mod cache;
 /// Module containing all typed constants
 pub mod consts;
@@ -35,9 +46,17 @@ mod dev;
 mod devno;
 mod encode;
 mod err;
+mod ownership;
 mod partition;
+mod parttype;
 mod probe;
+mod result;
+mod signature;
+mod sysfs;
+mod table;
 mod tag;
+#[cfg(test)]
+mod test_utils;
 mod topology;
 mod utils;
 mod version;
@@ -46,20 +65,34 @@ pub use uuid::Uuid;
 
 pub use libblkid_rs_sys::blkid_loff_t;
 
+#[cfg(blkid237supported)]
+pub use crate::probe::BlkidHint;
+
 pub use crate::{
+    alignment::Alignment,
+    ambiguity::{AmbiguityReport, Candidate},
+    attributes::{DosFlags, GptAttributes, PartitionFlags},
+    builder::ProbeBuilder,
     cache::BlkidCache,
     consts::*,
     dev::{BlkidDev, BlkidDevIter},
     devno::{BlkidDevno, maj_t, min_t},
     encode::{encode_string, safe_string},
     err::{BlkidErr, Result},
+    ownership::{Owner, Ownership, OwnershipPolicy},
     partition::{BlkidPartition, BlkidPartlist, BlkidParttable},
+    parttype::{PartitionType, RootArch},
     probe::{
-        BlkidProbe, get_partition_name, get_superblock_name, is_known_fs_type,
-        is_known_partition_type,
+        BlkidProbe, BlkidProbeValues, BlkidProbeValuesBytes, KnownPartitionTypes, KnownSuperblocks,
+        get_partition_name, get_superblock_name, is_known_fs_type, is_known_partition_type,
+        known_partition_types, known_superblocks,
     },
-    tag::{BlkidTagIter, parse_tag_string},
-    topology::BlkidTopology,
-    utils::{BlkidSectors, evaluate_spec, evaluate_tag, send_uevent},
+    result::{ProbeResult, ProbeValue},
+    signature::{Signature, SignatureBackup, SignatureKind, Signatures},
+    sysfs::{BlockDevice, DeviceGraph, Sysfs},
+    table::{PartitionEntry, PartitionKind, PartitionNode, PartitionTable, PartitionTree},
+    tag::{BlkidTagBytesIter, BlkidTagIter, parse_tag_string},
+    topology::{BlkidTopology, TopologyInfo},
+    utils::{BlkidBytes, BlkidSectors, evaluate_spec, evaluate_tag, send_uevent},
     version::{get_library_version, parse_version_string},
 };
@@ -10,16 +10,6 @@ macro_rules! str_ptr_to_owned {
     };
 }
 
-macro_rules! str_ptr_with_size_to_owned {
-    ($str_ptr:expr, $size:expr) => {
-        std::ffi::CStr::from_bytes_with_nul(unsafe {
-            std::slice::from_raw_parts($str_ptr as *const u8, $size)
-        })?
-        .to_str()?
-        .to_string()
-    };
-}
-
 macro_rules! errno {
     ($ret_expr:expr) => {
         match $ret_expr {
@@ -62,7 +52,8 @@ macro_rules! consts_enum_conv {
         $(
             #[$enum_meta]
         )*
-        #[derive(Hash, PartialEq, Eq)]
+        #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub enum $enum_ident {
             $(
                 $(
@@ -102,6 +93,7 @@ macro_rules! flags {
         $(
             #[$meta]
         )*
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct $flag_set_name(std::collections::HashSet<$enum_name>);
 
         impl $flag_set_name {
@@ -114,6 +106,11 @@ macro_rules! flags {
             pub fn new(vec: Vec<$enum_name>) -> Self {
                 $flag_set_name(vec.into_iter().collect())
             }
+
+            /// Check whether the flag set contains the given flag
+            pub fn contains(&self, flag: $enum_name) -> bool {
+                self.0.contains(&flag)
+            }
         }
 
         impl std::convert::TryFrom<$converted_flag_type> for $flag_set_name {
@@ -124,7 +121,10 @@ macro_rules! flags {
 
                 let mut vec = Vec::new();
                 for i in 0..std::mem::size_of::<$converted_flag_type>() * BITS_IN_A_BYTE {
-                    vec.push(<$enum_name>::try_from((1 << i) & v)?);
+                    let bit = (1 << i) & v;
+                    if bit != 0 {
+                        vec.push(<$enum_name>::try_from(bit)?);
+                    }
                 }
                 Ok(<$flag_set_name>::new(vec))
             }
@@ -0,0 +1,292 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashSet, path::Path};
+
+use crate::{
+    Result,
+    ambiguity::AmbiguityReport,
+    consts::{BlkidFltr, BlkidSafeprobeRet, BlkidSublks, BlkidSublksFlags, BlkidUsageFlag},
+    probe::BlkidProbe,
+    result::ProbeResult,
+};
+
+/// A signature that claims a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Owner {
+    /// A partition table
+    PartitionTable {
+        /// Partition table type such as `gpt` or `dos`
+        pt_type: String,
+        /// Partition table ID
+        id: Option<String>,
+        /// Number of partitions in the table
+        partitions: usize,
+    },
+    /// A filesystem
+    Filesystem {
+        /// Filesystem type such as `xfs`
+        fs_type: String,
+        /// Filesystem UUID
+        uuid: Option<String>,
+        /// Filesystem label
+        label: Option<String>,
+    },
+    /// An LVM physical volume
+    LvmPv {
+        /// UUID of the physical volume
+        uuid: Option<String>,
+    },
+    /// A member of a RAID array other than an LVM physical volume
+    Raid {
+        /// RAID type such as `linux_raid_member`
+        raid_type: String,
+        /// UUID of the array
+        uuid: Option<String>,
+    },
+    /// An encrypted container such as LUKS
+    Crypto {
+        /// Container type such as `crypto_LUKS`
+        crypto_type: String,
+        /// UUID of the container
+        uuid: Option<String>,
+    },
+    /// Any other signature, such as swap
+    Other {
+        /// Signature type such as `swap`
+        sig_type: String,
+        /// UUID associated with the signature
+        uuid: Option<String>,
+    },
+}
+
+impl Owner {
+    fn from_superblock(result: &ProbeResult) -> Option<Self> {
+        let sig_type = result.fs_type.clone()?;
+        let uuid = result.uuid_string();
+        Some(match result.usage {
+            _ if sig_type == "LVM2_member" => Owner::LvmPv { uuid },
+            Some(BlkidUsageFlag::Filesystem) => Owner::Filesystem {
+                fs_type: sig_type,
+                uuid,
+                label: result.label.clone(),
+            },
+            Some(BlkidUsageFlag::Raid) => Owner::Raid {
+                raid_type: sig_type,
+                uuid,
+            },
+            Some(BlkidUsageFlag::Crypto) => Owner::Crypto {
+                crypto_type: sig_type,
+                uuid,
+            },
+            _ => Owner::Other { sig_type, uuid },
+        })
+    }
+}
+
+/// The verdict on whether a device is in use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ownership {
+    /// No signatures were found that claim the device
+    Free,
+    /// The device is claimed by the given signatures. A partition table is
+    /// listed before a superblock found on the whole device.
+    Owned(Vec<Owner>),
+    /// Several conflicting superblock signatures were found so the owner of
+    /// the device cannot be determined safely. The report lists all of them.
+    Ambiguous(AmbiguityReport),
+}
+
+impl Ownership {
+    /// Whether the device is free to use.
+    pub fn is_free(&self) -> bool {
+        matches!(self, Ownership::Free)
+    }
+}
+
+/// The policy used to decide whether a device is free.
+///
+/// Probe errors are always returned as errors and never reported as a free
+/// device.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OwnershipPolicy {
+    /// Superblock and partition table types that do not claim a device, such
+    /// as `swap` to allow reusing a device with a stale swap signature.
+    /// Ignored types are not probed for so they cannot make the result
+    /// ambiguous.
+    pub ignored_types: HashSet<String>,
+    /// Whether a partition table without partitions leaves the device free
+    pub ignore_empty_partition_tables: bool,
+}
+
+impl OwnershipPolicy {
+    /// Check whether the device at `path` is free.
+    pub fn check(&self, path: &Path) -> Result<Ownership> {
+        self.check_probe(&mut BlkidProbe::new_from_filename(path)?)
+    }
+
+    /// Check whether the device associated with `probe` is free. This enables
+    /// superblock and partition probing on the probe and replaces its
+    /// superblock and partition type filters.
+    pub fn check_probe(&self, probe: &mut BlkidProbe) -> Result<Ownership> {
+        probe.enable_superblocks(true)?;
+        probe.set_superblock_flags(BlkidSublksFlags::new(vec![
+            BlkidSublks::Type,
+            BlkidSublks::Usage,
+            BlkidSublks::Uuid,
+            BlkidSublks::Label,
+        ]))?;
+        probe.enable_partitions(true)?;
+        // Setting a filter replaces any existing one; with no ignored types
+        // nothing is filtered.
+        let ignored = self
+            .ignored_types
+            .iter()
+            .map(|t| t.as_str())
+            .collect::<Vec<_>>();
+        probe.filter_superblock_type(BlkidFltr::Notin, &ignored)?;
+        probe.filter_partition_types(BlkidFltr::Notin, &ignored)?;
+
+        match probe.do_safeprobe()? {
+            BlkidSafeprobeRet::None => return Ok(Ownership::Free),
+            BlkidSafeprobeRet::Ambiguous => {
+                return Ok(Ownership::Ambiguous(
+                    probe.get_superblock_candidates(&ignored)?,
+                ));
+            }
+            BlkidSafeprobeRet::Success => (),
+        }
+
+        let result = probe.get_result()?;
+        let mut owners = Vec::new();
+        if let Some(ref pt_type) = result.pt_type {
+            let partitions = probe.get_partitions()?.number_of_partitions()? as usize;
+            if partitions > 0 || !self.ignore_empty_partition_tables {
+                owners.push(Owner::PartitionTable {
+                    pt_type: pt_type.clone(),
+                    id: result.pt_uuid.clone(),
+                    partitions,
+                });
+            }
+        }
+        owners.extend(Owner::from_superblock(&result));
+
+        if owners.is_empty() {
+            Ok(Ownership::Free)
+        } else {
+            Ok(Ownership::Owned(owners))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use crate::test_utils::{IMAGE_SIZE, TempDir, dos_image, ext2_image, ext2_swap_image};
+
+    fn check(policy: &OwnershipPolicy, data: &[u8]) -> Ownership {
+        policy
+            .check_probe(&mut BlkidProbe::from_bytes(data).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_owner_from_superblock() {
+        let result = |fs_type: &str, usage| ProbeResult {
+            fs_type: Some(fs_type.to_string()),
+            usage: Some(usage),
+            ..Default::default()
+        };
+        assert_eq!(
+            Owner::from_superblock(&result("LVM2_member", BlkidUsageFlag::Raid)),
+            Some(Owner::LvmPv { uuid: None })
+        );
+        assert_eq!(
+            Owner::from_superblock(&result("crypto_LUKS", BlkidUsageFlag::Crypto)),
+            Some(Owner::Crypto {
+                crypto_type: "crypto_LUKS".to_string(),
+                uuid: None
+            })
+        );
+        assert_eq!(
+            Owner::from_superblock(&result("swap", BlkidUsageFlag::Other)),
+            Some(Owner::Other {
+                sig_type: "swap".to_string(),
+                uuid: None
+            })
+        );
+        assert_eq!(Owner::from_superblock(&ProbeResult::default()), None);
+    }
+
+    #[test]
+    fn test_check_free_and_owned() {
+        let policy = OwnershipPolicy::default();
+        assert_eq!(check(&policy, &vec![0u8; IMAGE_SIZE]), Ownership::Free);
+        assert!(matches!(
+            check(&policy, &ext2_image()),
+            Ownership::Owned(ref owners) if matches!(
+                owners[..],
+                [Owner::Filesystem { ref fs_type, .. }] if fs_type == "ext2"
+            )
+        ));
+        assert_eq!(
+            check(&policy, &dos_image(&[(0x83, 2048, 4096)])),
+            Ownership::Owned(vec![Owner::PartitionTable {
+                pt_type: "dos".to_string(),
+                id: Some("12345678".to_string()),
+                partitions: 1,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_check_ambiguous() {
+        let mut policy = OwnershipPolicy::default();
+        match check(&policy, &ext2_swap_image()) {
+            Ownership::Ambiguous(report) => assert_eq!(report.conflicts().len(), 1),
+            ownership => panic!("expected an ambiguous result, got {ownership:?}"),
+        }
+
+        // An ignored type is not probed for so it cannot cause a conflict.
+        policy.ignored_types.insert("swap".to_string());
+        assert!(matches!(
+            check(&policy, &ext2_swap_image()),
+            Ownership::Owned(ref owners) if matches!(
+                owners[..],
+                [Owner::Filesystem { ref fs_type, .. }] if fs_type == "ext2"
+            )
+        ));
+    }
+
+    #[test]
+    fn test_check_empty_partition_table() {
+        let mut policy = OwnershipPolicy::default();
+        assert_eq!(
+            check(&policy, &dos_image(&[])),
+            Ownership::Owned(vec![Owner::PartitionTable {
+                pt_type: "dos".to_string(),
+                id: Some("12345678".to_string()),
+                partitions: 0,
+            }])
+        );
+        policy.ignore_empty_partition_tables = true;
+        assert_eq!(check(&policy, &dos_image(&[])), Ownership::Free);
+        assert!(!check(&policy, &dos_image(&[(0x83, 2048, 4096)])).is_free());
+    }
+
+    #[test]
+    fn test_check_probe_error() {
+        // libblkid fails to probe a regular file with topology probing
+        // enabled. The failure must not be reported as a free device.
+        let dir = TempDir::new("ownership-error");
+        let path = dir.path().join("disk.img");
+        fs::write(&path, vec![0u8; IMAGE_SIZE]).unwrap();
+        let mut probe = BlkidProbe::new_from_filename(&path).unwrap();
+        probe.enable_topology(true).unwrap();
+        assert!(OwnershipPolicy::default().check_probe(&mut probe).is_err());
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
     Result,
     devno::BlkidDevno,
     err::BlkidErr,
-    utils::{BlkidBytes, BlkidSectors},
+    utils::{BlkidBytes, BlkidSectors, LIBBLKID_SECTOR_SIZE},
 };
 
 /// A handle for working with partition tables.
@@ -79,15 +79,24 @@ impl<'a> BlkidPartition<'a> {
 
     /// Get the partition UUID or `None` if the partition table doesn't support it.
     pub fn get_uuid(&self) -> Result<Option<Uuid>> {
+        match self.get_id()? {
+            Some(s) => Ok(Some(Uuid::parse_str(&s).map_err(BlkidErr::Uuid)?)),
+            None => Ok(None),
+        }
+    }
+
+    // The partition ID as reported by libblkid. This is only a UUID for some
+    // partition table types; DOS partitions use the disk ID and partition number.
+    pub(crate) fn get_id(&self) -> Result<Option<String>> {
         let char_ptr =
             match option_ptr!(unsafe { libblkid_rs_sys::blkid_partition_get_uuid(self.1) }) {
                 Some(ptr) => ptr,
                 None => return Ok(None),
             };
-        match unsafe { CStr::from_ptr(char_ptr) }.to_str() {
-            Ok(s) => Ok(Some(Uuid::parse_str(s).map_err(BlkidErr::Uuid)?)),
-            Err(e) => Err(BlkidErr::UTF8(e)),
-        }
+        unsafe { CStr::from_ptr(char_ptr) }
+            .to_str()
+            .map(|s| Some(s.to_string()))
+            .map_err(BlkidErr::UTF8)
     }
 
     /// Get the partition number.
@@ -96,14 +105,20 @@ impl<'a> BlkidPartition<'a> {
             .map(|i| i as libc::c_uint)
     }
 
-    /// Get the start of the partition in units of sectors.
+    /// Get the start of the partition in units of 512-byte sectors.
     pub fn get_start(&self) -> BlkidSectors {
-        BlkidSectors::new(unsafe { libblkid_rs_sys::blkid_partition_get_start(self.1) })
+        BlkidSectors::new(
+            unsafe { libblkid_rs_sys::blkid_partition_get_start(self.1) },
+            LIBBLKID_SECTOR_SIZE,
+        )
     }
 
-    /// Get the size of the partition in units of sectors.
+    /// Get the size of the partition in units of 512-byte sectors.
     pub fn get_size(&self) -> BlkidSectors {
-        BlkidSectors::new(unsafe { libblkid_rs_sys::blkid_partition_get_size(self.1) })
+        BlkidSectors::new(
+            unsafe { libblkid_rs_sys::blkid_partition_get_size(self.1) },
+            LIBBLKID_SECTOR_SIZE,
+        )
     }
 
     /// Get the numeric partition type. Use `get_type_string` for the `String`
@@ -123,10 +138,8 @@ impl<'a> BlkidPartition<'a> {
         .to_string())
     }
 
-    /// Get the flags for the given partition.
-    ///
-    /// This method is not typed as the documentation does not specify which
-    /// constants are used as flags.
+    /// Get the raw flags for the given partition. Use `get_partition_flags`
+    /// for the flags decoded according to the partition table type.
     pub fn get_flags(&self) -> libc::c_ulonglong {
         unsafe { libblkid_rs_sys::blkid_partition_get_flags(self.1) }
     }
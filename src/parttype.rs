@@ -0,0 +1,360 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::{self, Display};
+
+use uuid::{Uuid, uuid};
+
+use crate::{Result, partition::BlkidPartition};
+
+/// CPU architecture of a Linux root partition as defined by the
+/// Discoverable Partitions Specification.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RootArch {
+    /// 32-bit x86
+    X86,
+    /// 64-bit x86
+    X86_64,
+    /// 32-bit ARM
+    Arm,
+    /// 64-bit ARM
+    Arm64,
+    /// Itanium
+    Ia64,
+    /// 64-bit LoongArch
+    LoongArch64,
+    /// 64-bit little endian POWER
+    Ppc64Le,
+    /// 64-bit RISC-V
+    RiscV64,
+    /// s390x
+    S390x,
+}
+
+const ROOT_ARCHES: &[(RootArch, Uuid)] = &[
+    (RootArch::X86, uuid!("44479540-f297-41b2-9af7-d131d5f0458a")),
+    (
+        RootArch::X86_64,
+        uuid!("4f68bce3-e8cd-4db1-96e7-fbcaf984b709"),
+    ),
+    (RootArch::Arm, uuid!("69dad710-2ce4-4e3c-b16c-21a1d49abed3")),
+    (
+        RootArch::Arm64,
+        uuid!("b921b045-1df0-41c3-af44-4c6f280d3fae"),
+    ),
+    (
+        RootArch::Ia64,
+        uuid!("993d8d3d-f80e-4225-855a-9daf8ed7ea97"),
+    ),
+    (
+        RootArch::LoongArch64,
+        uuid!("77055800-792c-4f94-b39a-98c91b762bb6"),
+    ),
+    (
+        RootArch::Ppc64Le,
+        uuid!("c31c45e6-3f39-412e-80fb-4809c4980599"),
+    ),
+    (
+        RootArch::RiscV64,
+        uuid!("72ec70a6-cf74-40e6-bd49-4bda08e8f224"),
+    ),
+    (
+        RootArch::S390x,
+        uuid!("5eead9a9-fe09-4a1e-a1d7-520d00531306"),
+    ),
+];
+
+/// A well-known partition type of a GPT or DOS partition table.
+///
+/// Types that exist in both GPT and DOS partition tables, such as
+/// `LinuxLvm`, convert to either representation. Unknown types are kept as
+/// `Gpt` or `Mbr` so that no information is lost.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionType {
+    /// Unused entry (MBR `0x00`)
+    Empty,
+    /// Linux filesystem data
+    LinuxFilesystem,
+    /// Linux swap
+    LinuxSwap,
+    /// Linux LVM physical volume
+    LinuxLvm,
+    /// Linux software RAID member
+    LinuxRaid,
+    /// Linux root partition for the given architecture
+    LinuxRoot(RootArch),
+    /// Linux `/home` partition
+    LinuxHome,
+    /// Linux `/srv` partition
+    LinuxSrv,
+    /// Linux `/var` partition
+    LinuxVar,
+    /// Linux LUKS encrypted partition
+    LinuxLuks,
+    /// Linux dm-crypt plain encrypted partition
+    LinuxDmCrypt,
+    /// Linux reserved
+    LinuxReserved,
+    /// Extended boot loader partition
+    XbootLdr,
+    /// EFI system partition
+    EfiSystem,
+    /// BIOS boot partition used by GRUB on GPT disks
+    BiosBoot,
+    /// Microsoft basic data; MBR `0x07` is also used for NTFS and exFAT
+    MicrosoftBasicData,
+    /// Microsoft reserved partition
+    MicrosoftReserved,
+    /// Windows recovery environment
+    WindowsRecovery,
+    /// FAT12 (MBR `0x01`)
+    Fat12,
+    /// FAT16 (MBR `0x06`)
+    Fat16,
+    /// FAT16 with LBA addressing (MBR `0x0e`)
+    Fat16Lba,
+    /// FAT32 (MBR `0x0b`)
+    Fat32,
+    /// FAT32 with LBA addressing (MBR `0x0c`)
+    Fat32Lba,
+    /// DOS extended partition (MBR `0x05`)
+    Extended,
+    /// DOS extended partition with LBA addressing (MBR `0x0f`)
+    ExtendedLba,
+    /// Linux extended partition (MBR `0x85`)
+    LinuxExtended,
+    /// Protective MBR partition of a GPT disk (MBR `0xee`)
+    GptProtective,
+    /// Any other GPT partition type GUID
+    Gpt(Uuid),
+    /// Any other MBR partition type code
+    Mbr(u8),
+}
+
+const GPT_TYPES: &[(PartitionType, Uuid)] = &[
+    (
+        PartitionType::LinuxFilesystem,
+        uuid!("0fc63daf-8483-4772-8e79-3d69d8477de4"),
+    ),
+    (
+        PartitionType::LinuxSwap,
+        uuid!("0657fd6d-a4ab-43c4-84e5-0933c84b4f4f"),
+    ),
+    (
+        PartitionType::LinuxLvm,
+        uuid!("e6d6d379-f507-44c2-a23c-238f2a3df928"),
+    ),
+    (
+        PartitionType::LinuxRaid,
+        uuid!("a19d880f-05fc-4d3b-a006-743f0f84911e"),
+    ),
+    (
+        PartitionType::LinuxHome,
+        uuid!("933ac7e1-2eb4-4f13-b844-0e14e2aef915"),
+    ),
+    (
+        PartitionType::LinuxSrv,
+        uuid!("3b8f8425-20e0-4f3b-907f-1a25a76f98e8"),
+    ),
+    (
+        PartitionType::LinuxVar,
+        uuid!("4d21b016-b534-45c2-a9fb-5c16e091fd2d"),
+    ),
+    (
+        PartitionType::LinuxLuks,
+        uuid!("ca7d7ccb-63ed-4c53-861c-1742536059cc"),
+    ),
+    (
+        PartitionType::LinuxDmCrypt,
+        uuid!("7ffec5c9-2d00-49b7-8941-3ea10a5586b7"),
+    ),
+    (
+        PartitionType::LinuxReserved,
+        uuid!("8da63339-0007-60c0-c436-083ac8230908"),
+    ),
+    (
+        PartitionType::XbootLdr,
+        uuid!("bc13c2ff-59e6-4262-a352-b275fd6f7172"),
+    ),
+    (
+        PartitionType::EfiSystem,
+        uuid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b"),
+    ),
+    (
+        PartitionType::BiosBoot,
+        uuid!("21686148-6449-6e6f-744e-656564454649"),
+    ),
+    (
+        PartitionType::MicrosoftBasicData,
+        uuid!("ebd0a0a2-b9e5-4433-87c0-68b6b72699c7"),
+    ),
+    (
+        PartitionType::MicrosoftReserved,
+        uuid!("e3c9e316-0b5c-4db8-817d-f92df00215ae"),
+    ),
+    (
+        PartitionType::WindowsRecovery,
+        uuid!("de94bba4-06d1-4d40-a16a-bfd50179d6ac"),
+    ),
+];
+
+const MBR_TYPES: &[(PartitionType, u8)] = &[
+    (PartitionType::Empty, 0x00),
+    (PartitionType::Fat12, 0x01),
+    (PartitionType::Extended, 0x05),
+    (PartitionType::Fat16, 0x06),
+    (PartitionType::MicrosoftBasicData, 0x07),
+    (PartitionType::Fat32, 0x0b),
+    (PartitionType::Fat32Lba, 0x0c),
+    (PartitionType::Fat16Lba, 0x0e),
+    (PartitionType::ExtendedLba, 0x0f),
+    (PartitionType::WindowsRecovery, 0x27),
+    (PartitionType::LinuxSwap, 0x82),
+    (PartitionType::LinuxFilesystem, 0x83),
+    (PartitionType::LinuxExtended, 0x85),
+    (PartitionType::LinuxLvm, 0x8e),
+    (PartitionType::GptProtective, 0xee),
+    (PartitionType::EfiSystem, 0xef),
+    (PartitionType::LinuxRaid, 0xfd),
+];
+
+impl PartitionType {
+    /// Get the partition type for a GPT partition type GUID.
+    pub fn from_gpt_guid(guid: Uuid) -> Self {
+        GPT_TYPES
+            .iter()
+            .find(|(_, g)| *g == guid)
+            .map(|(t, _)| *t)
+            .or_else(|| {
+                ROOT_ARCHES
+                    .iter()
+                    .find(|(_, g)| *g == guid)
+                    .map(|(arch, _)| PartitionType::LinuxRoot(*arch))
+            })
+            .unwrap_or(PartitionType::Gpt(guid))
+    }
+
+    /// Get the partition type for an MBR partition type code.
+    pub fn from_mbr_code(code: u8) -> Self {
+        MBR_TYPES
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(t, _)| *t)
+            .unwrap_or(PartitionType::Mbr(code))
+    }
+
+    /// Get the GPT partition type GUID or `None` if this type cannot be used in
+    /// a GPT partition table.
+    pub fn gpt_guid(&self) -> Option<Uuid> {
+        match self {
+            PartitionType::Gpt(guid) => Some(*guid),
+            PartitionType::LinuxRoot(arch) => {
+                ROOT_ARCHES.iter().find(|(a, _)| a == arch).map(|(_, g)| *g)
+            }
+            _ => GPT_TYPES.iter().find(|(t, _)| t == self).map(|(_, g)| *g),
+        }
+    }
+
+    /// Get the MBR partition type code or `None` if this type cannot be used
+    /// in a DOS partition table.
+    pub fn mbr_code(&self) -> Option<u8> {
+        match self {
+            PartitionType::Mbr(code) => Some(*code),
+            _ => MBR_TYPES.iter().find(|(t, _)| t == self).map(|(_, c)| *c),
+        }
+    }
+
+    // Partition types are only interpreted for the partition table types that
+    // define them; BSD and other tables reuse small integers for unrelated
+    // types.
+    pub(crate) fn from_raw(
+        pt_type: &str,
+        type_code: libc::c_int,
+        type_string: Option<&str>,
+    ) -> Option<Self> {
+        match pt_type {
+            "gpt" => Uuid::parse_str(type_string?)
+                .ok()
+                .map(PartitionType::from_gpt_guid),
+            "dos" => u8::try_from(type_code)
+                .ok()
+                .map(PartitionType::from_mbr_code),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PartitionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionType::Empty => write!(f, "Empty"),
+            PartitionType::LinuxFilesystem => write!(f, "Linux filesystem"),
+            PartitionType::LinuxSwap => write!(f, "Linux swap"),
+            PartitionType::LinuxLvm => write!(f, "Linux LVM"),
+            PartitionType::LinuxRaid => write!(f, "Linux RAID"),
+            PartitionType::LinuxRoot(arch) => write!(f, "Linux root ({arch:?})"),
+            PartitionType::LinuxHome => write!(f, "Linux home"),
+            PartitionType::LinuxSrv => write!(f, "Linux server data"),
+            PartitionType::LinuxVar => write!(f, "Linux variable data"),
+            PartitionType::LinuxLuks => write!(f, "Linux LUKS"),
+            PartitionType::LinuxDmCrypt => write!(f, "Linux dm-crypt"),
+            PartitionType::LinuxReserved => write!(f, "Linux reserved"),
+            PartitionType::XbootLdr => write!(f, "Linux extended boot"),
+            PartitionType::EfiSystem => write!(f, "EFI System"),
+            PartitionType::BiosBoot => write!(f, "BIOS boot"),
+            PartitionType::MicrosoftBasicData => write!(f, "Microsoft basic data"),
+            PartitionType::MicrosoftReserved => write!(f, "Microsoft reserved"),
+            PartitionType::WindowsRecovery => write!(f, "Windows recovery environment"),
+            PartitionType::Fat12 => write!(f, "FAT12"),
+            PartitionType::Fat16 => write!(f, "FAT16"),
+            PartitionType::Fat16Lba => write!(f, "FAT16 (LBA)"),
+            PartitionType::Fat32 => write!(f, "FAT32"),
+            PartitionType::Fat32Lba => write!(f, "FAT32 (LBA)"),
+            PartitionType::Extended => write!(f, "Extended"),
+            PartitionType::ExtendedLba => write!(f, "Extended (LBA)"),
+            PartitionType::LinuxExtended => write!(f, "Linux extended"),
+            PartitionType::GptProtective => write!(f, "GPT protective"),
+            PartitionType::Gpt(guid) => write!(f, "{guid}"),
+            PartitionType::Mbr(code) => write!(f, "{code:#04x}"),
+        }
+    }
+}
+
+impl BlkidPartition<'_> {
+    /// Get the typed partition type or `None` if the partition does not belong
+    /// to a GPT or DOS partition table.
+    pub fn get_partition_type(&self) -> Result<Option<PartitionType>> {
+        Ok(PartitionType::from_raw(
+            &self.get_table()?.get_type()?,
+            self.get_type(),
+            self.get_type_string().ok().as_deref(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let esp = PartitionType::from_gpt_guid(uuid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b"));
+        assert_eq!(esp, PartitionType::EfiSystem);
+        assert_eq!(esp.mbr_code(), Some(0xef));
+        assert_eq!(
+            PartitionType::from_mbr_code(0x8e).gpt_guid(),
+            Some(uuid!("e6d6d379-f507-44c2-a23c-238f2a3df928"))
+        );
+        assert_eq!(
+            PartitionType::from_gpt_guid(uuid!("b921b045-1df0-41c3-af44-4c6f280d3fae")),
+            PartitionType::LinuxRoot(RootArch::Arm64)
+        );
+        assert_eq!(PartitionType::from_mbr_code(0x42), PartitionType::Mbr(0x42));
+        assert_eq!(PartitionType::Mbr(0x42).gpt_guid(), None);
+        assert_eq!(PartitionType::BiosBoot.mbr_code(), None);
+        assert_eq!(PartitionType::from_raw("bsd", 7, None), None);
+    }
+}
@@ -4,40 +4,113 @@
 
 use std::{
     ffi::{CStr, CString},
-    os::unix::io::RawFd,
+    os::unix::io::{AsRawFd, OwnedFd, RawFd},
     path::Path,
     ptr,
 };
+#[cfg(target_os = "linux")]
+use std::{
+    fs::File,
+    io::{self, Write},
+    os::unix::io::FromRawFd,
+};
 
 use crate::{
     Result,
     consts::{
-        BlkidFltr, BlkidFullprobeRet, BlkidProbeRet, BlkidProbreqFlags, BlkidSafeprobeRet,
-        BlkidSublksFlags, BlkidUsageFlags,
+        BlkidFltr, BlkidFullprobeRet, BlkidPartsFlags, BlkidProbeRet, BlkidProbreqFlags,
+        BlkidSafeprobeRet, BlkidSublksFlags, BlkidUsageFlags,
     },
     devno::BlkidDevno,
     err::BlkidErr,
     partition::BlkidPartlist,
-    topology::BlkidTopology,
+    topology::{BlkidTopology, TopologyInfo},
+    utils::{BlkidSectors, LIBBLKID_SECTOR_SIZE},
 };
 
+#[cfg(blkid237supported)]
+use crate::version::check_library_version;
+
+/// A hint passed to the probing functions.
+#[cfg(blkid237supported)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlkidHint {
+    /// Offset in bytes of the last session on multi-session optical media
+    /// such as ISO9660 and UDF images (`session_offset`)
+    SessionOffset(u64),
+    /// A hint not known to these bindings given by name and value
+    Other(String, u64),
+}
+
 /// A structure for probing block devices.
-pub struct BlkidProbe(pub(super) libblkid_rs_sys::blkid_probe);
+///
+/// The optional file descriptor is the device assigned with
+/// `set_owned_device` and is closed after the probe is freed. The flag records
+/// whether the device is data held in memory assigned by `from_bytes`.
+pub struct BlkidProbe(
+    pub(super) libblkid_rs_sys::blkid_probe,
+    Option<OwnedFd>,
+    bool,
+);
 
 impl BlkidProbe {
     /// Allocate and create a new libblkid probe.
     pub fn new() -> Result<Self> {
-        Ok(BlkidProbe(errno_ptr!(unsafe {
-            libblkid_rs_sys::blkid_new_probe()
-        })?))
+        Ok(BlkidProbe(
+            errno_ptr!(unsafe { libblkid_rs_sys::blkid_new_probe() })?,
+            None,
+            false,
+        ))
     }
 
     /// Create a new probe from a filename.
     pub fn new_from_filename(filename: &Path) -> Result<Self> {
         let filename_cstring = CString::new(filename.to_str().ok_or(BlkidErr::InvalidConv)?)?;
-        Ok(BlkidProbe(errno_ptr!(unsafe {
-            libblkid_rs_sys::blkid_new_probe_from_filename(filename_cstring.as_ptr())
-        })?))
+        Ok(BlkidProbe(
+            errno_ptr!(unsafe {
+                libblkid_rs_sys::blkid_new_probe_from_filename(filename_cstring.as_ptr())
+            })?,
+            None,
+            false,
+        ))
+    }
+
+    /// Create a new probe that takes ownership of a file descriptor, such as
+    /// a `File`, and probes `size` bytes starting at `offset` bytes into the
+    /// file. A `size` of `0` probes to the end of the file.
+    ///
+    /// The file descriptor is closed when the probe is dropped.
+    pub fn new_from_fd<F>(
+        fd: F,
+        offset: libblkid_rs_sys::blkid_loff_t,
+        size: libblkid_rs_sys::blkid_loff_t,
+    ) -> Result<Self>
+    where
+        F: Into<OwnedFd>,
+    {
+        let mut probe = BlkidProbe::new()?;
+        probe.set_owned_device(fd, offset, size)?;
+        Ok(probe)
+    }
+
+    /// Create a new probe for data held in memory, such as a copy of the
+    /// start of a device. The data is copied into an anonymous in-memory file
+    /// that can be probed for superblocks and partitions.
+    ///
+    /// libblkid only reads topology information from block devices so
+    /// topology probing is not supported and `enable_topology(true)` returns an
+    /// error for the probe.
+    #[cfg(target_os = "linux")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let fd = unsafe { libc::memfd_create(c"libblkid-rs".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(BlkidErr::IO(io::Error::last_os_error()));
+        }
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        file.write_all(bytes)?;
+        let mut probe = BlkidProbe::new_from_fd(file, 0, 0)?;
+        probe.2 = true;
+        Ok(probe)
     }
 
     /// Reset the probe.
@@ -56,13 +129,36 @@ impl BlkidProbe {
     }
 
     /// Assign the device to the probe control structure.
+    ///
+    /// The file descriptor must remain open for as long as the probe uses it.
+    /// Use `set_owned_device` to have the probe keep it open.
     pub fn set_device(
         &mut self,
         fd: RawFd,
         offset: libblkid_rs_sys::blkid_loff_t,
         size: libblkid_rs_sys::blkid_loff_t,
     ) -> Result<()> {
-        errno!(unsafe { libblkid_rs_sys::blkid_probe_set_device(self.0, fd, offset, size) })
+        errno!(unsafe { libblkid_rs_sys::blkid_probe_set_device(self.0, fd, offset, size) })?;
+        self.2 = false;
+        Ok(())
+    }
+
+    /// Assign the device to the probe control structure and take ownership of
+    /// its file descriptor so that it remains open for as long as the probe
+    /// uses it. A previously assigned owned file descriptor is closed.
+    pub fn set_owned_device<F>(
+        &mut self,
+        fd: F,
+        offset: libblkid_rs_sys::blkid_loff_t,
+        size: libblkid_rs_sys::blkid_loff_t,
+    ) -> Result<()>
+    where
+        F: Into<OwnedFd>,
+    {
+        let fd = fd.into();
+        self.set_device(fd.as_raw_fd(), offset, size)?;
+        self.1 = Some(fd);
+        Ok(())
     }
 
     /// Get the device number associated with the probe device.
@@ -96,11 +192,55 @@ impl BlkidProbe {
         unsafe { libblkid_rs_sys::blkid_probe_get_sectorsize(self.0) }
     }
 
+    /// Override the sector size of the attached device, for example to probe
+    /// an image of a device with 4096-byte sectors.
+    pub fn set_sector_size(&mut self, size: libc::c_uint) -> Result<()> {
+        errno!(unsafe { libblkid_rs_sys::blkid_probe_set_sectorsize(self.0, size) })
+    }
+
+    /// Get the number of whole sectors of the device using the sector size
+    /// returned by `get_sector_size`.
+    pub fn get_sectors(&self) -> Result<BlkidSectors> {
+        // libblkid always counts in 512-byte sectors.
+        errno_with_ret!(unsafe { libblkid_rs_sys::blkid_probe_get_sectors(self.0) })
+            .map(|sectors| BlkidSectors::new(sectors, LIBBLKID_SECTOR_SIZE))?
+            .checked_bytes()
+            .ok_or(BlkidErr::InvalidConv)?
+            .sectors_of_size_floor(self.get_sector_size())
+    }
+
     /// Get a file descriptor associated with the given device.
     pub fn get_fd(&self) -> Result<RawFd> {
         errno_with_ret!(unsafe { libblkid_rs_sys::blkid_probe_get_fd(self.0) })
     }
 
+    /// Set a hint for the probing functions. Hints are kept until
+    /// `reset_hints` is called.
+    ///
+    /// Requires libblkid 2.37 or later.
+    #[cfg(blkid237supported)]
+    pub fn set_hint(&mut self, hint: &BlkidHint) -> Result<()> {
+        check_library_version(2, 37)?;
+        let (name, value) = match *hint {
+            BlkidHint::SessionOffset(value) => ("session_offset", value),
+            BlkidHint::Other(ref name, value) => (name.as_str(), value),
+        };
+        let name_cstring = CString::new(name)?;
+        errno!(unsafe {
+            libblkid_rs_sys::blkid_probe_set_hint(self.0, name_cstring.as_ptr(), value)
+        })
+    }
+
+    /// Remove all hints set with `set_hint`.
+    ///
+    /// Requires libblkid 2.37 or later.
+    #[cfg(blkid237supported)]
+    pub fn reset_hints(&mut self) -> Result<()> {
+        check_library_version(2, 37)?;
+        unsafe { libblkid_rs_sys::blkid_probe_reset_hints(self.0) };
+        Ok(())
+    }
+
     /// Enable superblock probing.
     pub fn enable_superblocks(&mut self, enable: bool) -> Result<()> {
         errno!(unsafe { libblkid_rs_sys::blkid_probe_enable_superblocks(self.0, enable.into()) })
@@ -159,7 +299,17 @@ impl BlkidProbe {
     }
 
     /// Enable topology probing.
+    ///
+    /// Topology information is only available for block devices; with any
+    /// other device libblkid fails every probe while topology probing is
+    /// enabled. Enabling topology probing for data held in memory returns an
+    /// error.
     pub fn enable_topology(&mut self, enable: bool) -> Result<()> {
+        if enable && self.2 {
+            return Err(BlkidErr::Other(
+                "Topology probing is not supported for data held in memory".to_string(),
+            ));
+        }
         errno!(unsafe { libblkid_rs_sys::blkid_probe_enable_topology(self.0, enable.into()) })
     }
 
@@ -174,6 +324,12 @@ impl BlkidProbe {
         })?))
     }
 
+    /// Get an owned snapshot of the blkid topology of devices that is not
+    /// overwritten by subsequent probing.
+    pub fn get_topology_info(&mut self) -> Result<TopologyInfo> {
+        self.get_topology().map(|topology| topology.info())
+    }
+
     /// Enable partition probing.
     pub fn enable_partitions(&mut self, enable: bool) -> Result<()> {
         errno!(unsafe { libblkid_rs_sys::blkid_probe_enable_partitions(self.0, enable.into()) })
@@ -218,6 +374,11 @@ impl BlkidProbe {
         })
     }
 
+    /// Set the partition probing flags.
+    pub fn set_partition_flags(&mut self, flags: BlkidPartsFlags) -> Result<()> {
+        errno!(unsafe { libblkid_rs_sys::blkid_probe_set_partitions_flags(self.0, flags.into()) })
+    }
+
     /// Get list of probed partitions.
     pub fn get_partitions(&mut self) -> Result<BlkidPartlist<'_>> {
         Ok(BlkidPartlist::new(errno_ptr!(unsafe {
@@ -255,9 +416,34 @@ impl BlkidProbe {
             .map(|v| v as usize)
     }
 
+    /// Get an iterator over the tags and values of all entries in the probe.
+    ///
+    /// The iterator borrows the probe so the values cannot change during
+    /// iteration. It can be collected into a map with
+    /// `probe.values()?.collect::<Result<BTreeMap<_, _>>>()`.
+    pub fn values(&self) -> Result<BlkidProbeValues<'_>> {
+        Ok(BlkidProbeValues {
+            probe: self,
+            index: 0,
+            len: self.numof_values()?,
+        })
+    }
+
     /// Get the tag and value of an entry by the index in the range
     /// `0..(self.numof_values())`.
     pub fn get_value(&self, num: libc::c_uint) -> Result<(String, String)> {
+        let (name, data) = self.get_value_bytes(num)?;
+        let data = std::str::from_utf8(data)?.to_string();
+        Ok((name, data))
+    }
+
+    /// Get the tag and value of an entry by the index in the range
+    /// `0..(self.numof_values())` without converting the value to a string.
+    ///
+    /// String values are returned without their terminating null byte and
+    /// binary values such as `SBMAGIC`, `LABEL_RAW`, and `UUID_RAW` are
+    /// returned as stored by libblkid.
+    pub fn get_value_bytes(&self, num: libc::c_uint) -> Result<(String, &[u8])> {
         let num_values = self.numof_values()?;
         if num as usize >= num_values {
             return Err(BlkidErr::Other(format!(
@@ -266,25 +452,43 @@ impl BlkidProbe {
             )));
         }
 
+        self.get_value_raw(num as libc::c_int)
+    }
+
+    /// Get the tag and the unconverted data of an entry by the index without
+    /// the terminating null byte of string values. No bounds checking is done
+    /// beyond what libblkid does internally.
+    pub(crate) fn get_value_raw(&self, num: libc::c_int) -> Result<(String, &[u8])> {
         let mut name: *const libc::c_char = ptr::null();
         let mut data: *const libc::c_char = ptr::null();
         let mut size: usize = 0;
         errno!(unsafe {
             libblkid_rs_sys::blkid_probe_get_value(
                 self.0,
-                num as libc::c_int,
+                num,
                 &mut name as *mut _,
                 &mut data as *mut _,
                 &mut size as *mut _,
             )
         })?;
         let name = str_ptr_to_owned!(name);
-        let data = str_ptr_with_size_to_owned!(data, size);
+        let data = strip_nul(&name, unsafe {
+            std::slice::from_raw_parts(data as *const u8, size)
+        });
         Ok((name, data))
     }
 
     /// Get the value for a tag with the given name.
     pub fn lookup_value(&self, name: &str) -> Result<String> {
+        let data = self.lookup_value_bytes(name)?;
+        Ok(std::str::from_utf8(data)?.to_string())
+    }
+
+    /// Get the value for a tag with the given name without converting it to a
+    /// string.
+    ///
+    /// See `get_value_bytes` for the format of the returned value.
+    pub fn lookup_value_bytes(&self, name: &str) -> Result<&[u8]> {
         let name_cstring = CString::new(name)?;
 
         let mut data: *const libc::c_char = ptr::null();
@@ -297,8 +501,9 @@ impl BlkidProbe {
                 &mut size as *mut _,
             )
         })?;
-        let data = str_ptr_with_size_to_owned!(data, size);
-        Ok(data)
+        Ok(strip_nul(name, unsafe {
+            std::slice::from_raw_parts(data as *const u8, size)
+        }))
     }
 
     /// Check whether the given name exists in a probe.
@@ -323,6 +528,80 @@ impl BlkidProbe {
     }
 }
 
+/// Iterator over the tags and values of all entries in a probe.
+pub struct BlkidProbeValues<'a> {
+    probe: &'a BlkidProbe,
+    index: usize,
+    len: usize,
+}
+
+impl<'a> BlkidProbeValues<'a> {
+    /// Convert this iterator into an iterator that yields values without
+    /// converting them to strings.
+    ///
+    /// See `BlkidProbe::get_value_bytes` for the format of the values.
+    pub fn bytes(self) -> BlkidProbeValuesBytes<'a> {
+        BlkidProbeValuesBytes(self)
+    }
+
+    fn next_bytes(&mut self) -> Option<Result<(String, &'a [u8])>> {
+        if self.index >= self.len {
+            return None;
+        }
+        let value = self.probe.get_value_raw(self.index as libc::c_int);
+        self.index += 1;
+        Some(value)
+    }
+}
+
+impl Iterator for BlkidProbeValues<'_> {
+    type Item = Result<(String, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_bytes().map(|value| {
+            let (name, data) = value?;
+            Ok((name, std::str::from_utf8(data)?.to_string()))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for BlkidProbeValues<'_> {}
+
+/// Iterator over the tags and unconverted values of all entries in a probe.
+pub struct BlkidProbeValuesBytes<'a>(BlkidProbeValues<'a>);
+
+impl<'a> Iterator for BlkidProbeValuesBytes<'a> {
+    type Item = Result<(String, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_bytes()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for BlkidProbeValuesBytes<'_> {}
+
+// Values that libblkid stores as binary data rather than as null-terminated
+// strings.
+const BINARY_VALUES: &[&str] = &["SBMAGIC", "PTMAGIC", "LABEL_RAW", "UUID_RAW"];
+
+// Remove the terminating null byte of a string value.
+fn strip_nul<'a>(name: &str, data: &'a [u8]) -> &'a [u8] {
+    if BINARY_VALUES.contains(&name) {
+        data
+    } else {
+        data.strip_suffix(&[0]).unwrap_or(data)
+    }
+}
+
 impl Drop for BlkidProbe {
     fn drop(&mut self) {
         unsafe { libblkid_rs_sys::blkid_free_probe(self.0) }
@@ -341,7 +620,7 @@ pub fn is_known_fs_type(fstype: &str) -> Result<bool> {
 ///
 /// This method in libblkid exposes implementation details of the library. There
 /// is no way to map indices to types without duplicating logic inside and outside
-/// of the library.
+/// of the library. Use `known_superblocks` to list all known types.
 pub fn get_superblock_name(
     index: usize,
     get_name: bool,
@@ -364,11 +643,47 @@ pub fn get_superblock_name(
             },
         )
     })?;
-    let name_option = Some(unsafe { CStr::from_ptr(name_ptr) }.to_str()?);
-    let flags_option = Some(BlkidUsageFlags::try_from(flags)?);
+    let name_option = if get_name {
+        Some(unsafe { CStr::from_ptr(name_ptr) }.to_str()?)
+    } else {
+        None
+    };
+    let flags_option = if get_flags {
+        Some(BlkidUsageFlags::try_from(flags)?)
+    } else {
+        None
+    };
     Ok((name_option, flags_option))
 }
 
+/// Get an iterator over the names and usage flags of all superblock types
+/// known to libblkid. A type whose name or flags cannot be converted is
+/// returned as an error and iteration continues with the next type.
+pub fn known_superblocks() -> KnownSuperblocks {
+    KnownSuperblocks { index: 0 }
+}
+
+/// Iterator over all superblock types known to libblkid.
+pub struct KnownSuperblocks {
+    index: usize,
+}
+
+impl Iterator for KnownSuperblocks {
+    type Item = Result<(&'static str, BlkidUsageFlags)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // libblkid returns an error code only if the index is out of range.
+        let item = match get_superblock_name(self.index, true, true) {
+            Err(BlkidErr::LibErr(_)) => return None,
+            Ok((Some(name), Some(flags))) => Ok((name, flags)),
+            Ok(_) => Err(BlkidErr::InvalidConv),
+            Err(e) => Err(e),
+        };
+        self.index += 1;
+        Some(item)
+    }
+}
+
 /// Checks whether the name provided is a known partition type.
 pub fn is_known_partition_type(type_: &str) -> bool {
     let type_cstring = match CString::new(type_) {
@@ -383,10 +698,116 @@ pub fn is_known_partition_type(type_: &str) -> bool {
 ///
 /// This method in libblkid exposes implementation details of the library. There
 /// is no way to map indices to types without duplicating logic inside and outside
-/// of the library.
+/// of the library. Use `known_partition_types` to list all known types.
 pub fn get_partition_name(index: usize) -> Result<&'static str> {
     let mut name_ptr: *const libc::c_char = ptr::null();
     errno!(unsafe { libblkid_rs_sys::blkid_partitions_get_name(index, &mut name_ptr as *mut _) })?;
     let name = unsafe { CStr::from_ptr(name_ptr) }.to_str()?;
     Ok(name)
 }
+
+/// Get an iterator over the names of all partition table types known to
+/// libblkid. A type whose name cannot be converted is returned as an error and
+/// iteration continues with the next type.
+pub fn known_partition_types() -> KnownPartitionTypes {
+    KnownPartitionTypes { index: 0 }
+}
+
+/// Iterator over all partition table types known to libblkid.
+pub struct KnownPartitionTypes {
+    index: usize,
+}
+
+impl Iterator for KnownPartitionTypes {
+    type Item = Result<&'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // libblkid returns an error code only if the index is out of range.
+        let item = match get_partition_name(self.index) {
+            Err(BlkidErr::LibErr(_)) => return None,
+            item => item,
+        };
+        self.index += 1;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::consts::{BlkidSublks, BlkidUsageFlag};
+
+    #[test]
+    fn test_enable_topology() {
+        let mut probe = BlkidProbe::new().unwrap();
+        probe.enable_topology(true).unwrap();
+        probe.enable_topology(false).unwrap();
+    }
+
+    #[test]
+    fn test_probe_bytes() {
+        let mut data = vec![0u8; 1 << 16];
+        data[0x438..0x43a].copy_from_slice(&[0x53, 0xef]);
+        let mut probe = BlkidProbe::from_bytes(&data).unwrap();
+        assert!(probe.enable_topology(true).is_err());
+        probe.enable_partitions(true).unwrap();
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Success);
+        assert_eq!(probe.lookup_value("TYPE").unwrap(), "ext2");
+        assert_eq!(probe.lookup_value_bytes("TYPE").unwrap(), b"ext2");
+        let (name, value) = probe.get_value_bytes(0).unwrap();
+        assert_eq!(probe.lookup_value_bytes(&name).unwrap(), value);
+        assert_ne!(value.last(), Some(&0));
+        probe
+            .set_superblock_flags(BlkidSublksFlags::new(vec![BlkidSublks::Magic]))
+            .unwrap();
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Success);
+        assert_eq!(probe.lookup_value_bytes("SBMAGIC").unwrap(), &[0x53, 0xef]);
+        assert_eq!(probe.get_size(), 1 << 16);
+        assert_eq!(probe.get_sectors().unwrap(), BlkidSectors::new(128, 512));
+        probe.set_sector_size(4096).unwrap();
+        let sectors = probe.get_sectors().unwrap();
+        assert_eq!(sectors.sector_size(), 4096);
+        assert_eq!(*sectors.as_ref(), 16);
+    }
+
+    #[cfg(blkid237supported)]
+    #[test]
+    fn test_hints() {
+        let mut data = vec![0u8; 1 << 16];
+        data[0x438..0x43a].copy_from_slice(&[0x53, 0xef]);
+        let mut probe = BlkidProbe::from_bytes(&data).unwrap();
+        probe.set_hint(&BlkidHint::SessionOffset(0)).unwrap();
+        assert_eq!(probe.do_safeprobe().unwrap(), BlkidSafeprobeRet::Success);
+        assert_eq!(probe.lookup_value("TYPE").unwrap(), "ext2");
+        probe.reset_hints().unwrap();
+        assert!(
+            probe
+                .set_hint(&BlkidHint::Other("session\0offset".to_string(), 0))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_known_types() {
+        let superblocks = known_superblocks()
+            .collect::<Result<HashMap<_, _>>>()
+            .unwrap();
+        assert!(superblocks["ext4"].contains(BlkidUsageFlag::Filesystem));
+        assert!(superblocks["swap"].contains(BlkidUsageFlag::Other));
+        assert!(
+            superblocks
+                .keys()
+                .all(|name| is_known_fs_type(name).unwrap())
+        );
+        let partition_types = known_partition_types().collect::<Result<Vec<_>>>().unwrap();
+        assert!(partition_types.contains(&"gpt"));
+        assert!(partition_types.into_iter().all(is_known_partition_type));
+
+        let (name, flags) = get_superblock_name(0, false, true).unwrap();
+        assert!(name.is_none());
+        assert!(flags.is_some());
+    }
+}
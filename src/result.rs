@@ -0,0 +1,268 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use uuid::Uuid;
+
+use crate::{Result, consts::BlkidUsageFlag, devno::BlkidDevno, probe::BlkidProbe};
+
+/// A typed snapshot of the values reported by a probe.
+///
+/// Each field corresponds to one of the value names defined by libblkid and is
+/// `None` if the probe did not report that value. A value that cannot be
+/// represented by its typed field, such as a vfat serial number in place of a
+/// UUID, is kept as reported under its original name in `extra`, along with
+/// all values that have no dedicated field.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProbeResult {
+    /// Filesystem or other superblock type (`TYPE`)
+    pub fs_type: Option<String>,
+    /// Secondary compatible filesystem type (`SEC_TYPE`)
+    pub sec_type: Option<String>,
+    /// Usage of the superblock (`USAGE`)
+    pub usage: Option<BlkidUsageFlag>,
+    /// Filesystem UUID (`UUID`)
+    pub uuid: Option<Uuid>,
+    /// Filesystem label (`LABEL`)
+    pub label: Option<String>,
+    /// Filesystem version (`VERSION`)
+    pub version: Option<String>,
+    /// Superblock magic bytes (`SBMAGIC`)
+    pub sbmagic: Option<Vec<u8>>,
+    /// Offset of the superblock magic in bytes (`SBMAGIC_OFFSET`)
+    pub sbmagic_offset: Option<u64>,
+    /// Partition table type (`PTTYPE`)
+    pub pt_type: Option<String>,
+    /// Partition table ID (`PTUUID`)
+    pub pt_uuid: Option<String>,
+    /// Partition table magic bytes (`PTMAGIC`)
+    pub ptmagic: Option<Vec<u8>>,
+    /// Offset of the partition table magic in bytes (`PTMAGIC_OFFSET`)
+    pub ptmagic_offset: Option<u64>,
+    /// Partition table type of the partition (`PART_ENTRY_SCHEME`)
+    pub part_entry_scheme: Option<String>,
+    /// Partition name (`PART_ENTRY_NAME`)
+    pub part_entry_name: Option<String>,
+    /// Partition ID (`PART_ENTRY_UUID`)
+    ///
+    /// This is only a UUID for some partition table types so it is kept as a
+    /// `String`.
+    pub part_entry_uuid: Option<String>,
+    /// Partition type as a GUID or a hex code (`PART_ENTRY_TYPE`)
+    pub part_entry_type: Option<String>,
+    /// Partition flags (`PART_ENTRY_FLAGS`)
+    pub part_entry_flags: Option<u64>,
+    /// Partition number (`PART_ENTRY_NUMBER`)
+    pub part_entry_number: Option<u32>,
+    /// Start of the partition in sectors (`PART_ENTRY_OFFSET`)
+    pub part_entry_offset: Option<u64>,
+    /// Size of the partition in sectors (`PART_ENTRY_SIZE`)
+    pub part_entry_size: Option<u64>,
+    /// Device number of the whole disk (`PART_ENTRY_DISK`)
+    pub part_entry_disk: Option<BlkidDevno>,
+    /// All values without a typed field or that failed to convert to the
+    /// type of their field
+    pub extra: BTreeMap<String, ProbeValue>,
+}
+
+/// A value reported by a probe that is not stored in a typed field of
+/// `ProbeResult`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum ProbeValue {
+    /// A value that is valid UTF8
+    String(String),
+    /// A binary value or a string value that is not valid UTF8
+    Bytes(Vec<u8>),
+}
+
+impl ProbeValue {
+    /// Get the value as a string if it is valid UTF8.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            ProbeValue::String(ref s) => Some(s),
+            ProbeValue::Bytes(_) => None,
+        }
+    }
+
+    /// Get the value as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        match *self {
+            ProbeValue::String(ref s) => s.as_bytes(),
+            ProbeValue::Bytes(ref data) => data,
+        }
+    }
+}
+
+impl From<&[u8]> for ProbeValue {
+    fn from(data: &[u8]) -> Self {
+        match std::str::from_utf8(data) {
+            Ok(s) => ProbeValue::String(s.to_string()),
+            Err(_) => ProbeValue::Bytes(data.to_vec()),
+        }
+    }
+}
+
+impl ProbeResult {
+    /// Build a result from all values currently reported by the probe.
+    pub fn from_probe(probe: &BlkidProbe) -> Result<Self> {
+        let mut result = ProbeResult::default();
+        for value in probe.values()?.bytes() {
+            let (name, data) = value?;
+            if !result.set(&name, data) {
+                result.extra.insert(name, ProbeValue::from(data));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Get the UUID as a string, including UUIDs in other formats such as
+    /// vfat serial numbers that are kept in `extra`.
+    pub fn uuid_string(&self) -> Option<String> {
+        self.uuid
+            .map(|uuid| uuid.to_string())
+            .or_else(|| self.extra_str("UUID").map(|uuid| uuid.to_string()))
+    }
+
+    /// Get a value from `extra` as a string if it is valid UTF8.
+    pub fn extra_str(&self, name: &str) -> Option<&str> {
+        self.extra.get(name).and_then(|value| value.as_str())
+    }
+
+    // Store the value in its typed field. Returns false if there is no field
+    // for the value or the conversion failed.
+    fn set(&mut self, name: &str, data: &[u8]) -> bool {
+        fn string(data: &[u8]) -> Option<String> {
+            std::str::from_utf8(data).ok().map(|s| s.to_string())
+        }
+
+        fn parse<T: FromStr>(data: &[u8]) -> Option<T> {
+            std::str::from_utf8(data).ok()?.parse().ok()
+        }
+
+        fn store<T>(field: &mut Option<T>, value: Option<T>) -> bool {
+            let is_some = value.is_some();
+            *field = value;
+            is_some
+        }
+
+        match name {
+            "TYPE" => store(&mut self.fs_type, string(data)),
+            "SEC_TYPE" => store(&mut self.sec_type, string(data)),
+            "USAGE" => store(
+                &mut self.usage,
+                match data {
+                    b"filesystem" => Some(BlkidUsageFlag::Filesystem),
+                    b"raid" => Some(BlkidUsageFlag::Raid),
+                    b"crypto" => Some(BlkidUsageFlag::Crypto),
+                    b"other" => Some(BlkidUsageFlag::Other),
+                    _ => None,
+                },
+            ),
+            "UUID" => store(&mut self.uuid, parse(data)),
+            "LABEL" => store(&mut self.label, string(data)),
+            "VERSION" => store(&mut self.version, string(data)),
+            "SBMAGIC" => store(&mut self.sbmagic, Some(data.to_vec())),
+            "SBMAGIC_OFFSET" => store(&mut self.sbmagic_offset, parse(data)),
+            "PTTYPE" => store(&mut self.pt_type, string(data)),
+            "PTUUID" => store(&mut self.pt_uuid, string(data)),
+            "PTMAGIC" => store(&mut self.ptmagic, Some(data.to_vec())),
+            "PTMAGIC_OFFSET" => store(&mut self.ptmagic_offset, parse(data)),
+            "PART_ENTRY_SCHEME" => store(&mut self.part_entry_scheme, string(data)),
+            "PART_ENTRY_NAME" => store(&mut self.part_entry_name, string(data)),
+            "PART_ENTRY_UUID" => store(&mut self.part_entry_uuid, string(data)),
+            "PART_ENTRY_TYPE" => store(&mut self.part_entry_type, string(data)),
+            "PART_ENTRY_FLAGS" => store(
+                &mut self.part_entry_flags,
+                string(data).and_then(|s| {
+                    u64::from_str_radix(s.trim_start_matches("0x").trim_start_matches("0X"), 16)
+                        .ok()
+                }),
+            ),
+            "PART_ENTRY_NUMBER" => store(&mut self.part_entry_number, parse(data)),
+            "PART_ENTRY_OFFSET" => store(&mut self.part_entry_offset, parse(data)),
+            "PART_ENTRY_SIZE" => store(&mut self.part_entry_size, parse(data)),
+            "PART_ENTRY_DISK" => store(
+                &mut self.part_entry_disk,
+                string(data).and_then(|s| {
+                    let (major, minor) = s.split_once(':')?;
+                    Some(BlkidDevno::from_device_numbers(
+                        major.parse().ok()?,
+                        minor.parse().ok()?,
+                    ))
+                }),
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl BlkidProbe {
+    /// Get a typed snapshot of all values found by the last probing
+    /// operation.
+    pub fn get_result(&self) -> Result<ProbeResult> {
+        ProbeResult::from_probe(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_set_typed_values() {
+        let mut result = ProbeResult::default();
+        assert!(result.set("TYPE", b"xfs"));
+        assert!(result.set("USAGE", b"filesystem"));
+        assert!(result.set("UUID", b"2d4f8e2a-5b3e-4a6c-9f0e-8c1d2b3a4f5e"));
+        assert!(result.set("SBMAGIC", b"XFSB"));
+        assert!(result.set("SBMAGIC_OFFSET", b"0"));
+        assert!(result.set("PART_ENTRY_FLAGS", b"0x80"));
+        assert!(result.set("PART_ENTRY_DISK", b"8:0"));
+        assert_eq!(result.fs_type.as_deref(), Some("xfs"));
+        assert_eq!(result.usage, Some(BlkidUsageFlag::Filesystem));
+        assert_eq!(result.sbmagic.as_deref(), Some(&b"XFSB"[..]));
+        assert_eq!(result.sbmagic_offset, Some(0));
+        assert_eq!(result.part_entry_flags, Some(0x80));
+        assert_eq!(
+            result.part_entry_disk,
+            Some(BlkidDevno::from_device_numbers(8, 0))
+        );
+    }
+
+    #[test]
+    fn test_set_untyped_values() {
+        let mut result = ProbeResult::default();
+        assert!(!result.set("UUID", b"1234-ABCD"));
+        assert!(!result.set("BLOCK_SIZE", b"4096"));
+        assert!(result.uuid.is_none());
+    }
+
+    #[test]
+    fn test_extra_values() {
+        assert_eq!(
+            ProbeValue::from(&b"4096"[..]),
+            ProbeValue::String("4096".to_string())
+        );
+        assert_eq!(
+            ProbeValue::from(&[0x53, 0xef][..]),
+            ProbeValue::Bytes(vec![0x53, 0xef])
+        );
+
+        let mut data = vec![0u8; 1 << 16];
+        data[0x438..0x43a].copy_from_slice(&[0x53, 0xef]);
+        let mut probe = BlkidProbe::from_bytes(&data).unwrap();
+        probe.do_safeprobe().unwrap();
+        let result = probe.get_result().unwrap();
+        assert_eq!(result.fs_type.as_deref(), Some("ext2"));
+        assert_eq!(
+            result.extra.get("BLOCK_SIZE"),
+            Some(&ProbeValue::String("1024".to_string()))
+        );
+        assert_eq!(result.extra_str("BLOCK_SIZE"), Some("1024"));
+    }
+}
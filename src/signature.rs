@@ -0,0 +1,437 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::{
+        fs::{FileExt, OpenOptionsExt},
+        io::AsRawFd,
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Result,
+    consts::{
+        BlkidParts, BlkidPartsFlags, BlkidProbeRet, BlkidSublks, BlkidSublksFlags, BlkidUsageFlag,
+    },
+    err::BlkidErr,
+    probe::BlkidProbe,
+    result::ProbeResult,
+};
+
+/// The probing chain that detected a signature.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// A filesystem, RAID, crypto, or other superblock
+    Superblock,
+    /// A partition table
+    PartitionTable,
+}
+
+/// A signature detected on a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    /// The probing chain that detected the signature
+    pub kind: SignatureKind,
+    /// The superblock type (`TYPE`) or partition table type (`PTTYPE`)
+    pub sig_type: String,
+    /// Offset of the magic bytes in bytes from the start of the probing area
+    pub offset: u64,
+    /// The magic bytes identifying the signature
+    pub magic: Vec<u8>,
+    /// Usage of a superblock; `None` for partition tables
+    pub usage: Option<BlkidUsageFlag>,
+    /// UUID of the filesystem or ID of the partition table
+    pub uuid: Option<String>,
+    /// Label of the filesystem
+    pub label: Option<String>,
+}
+
+impl Signature {
+    // Signatures without magic bytes cannot be located on the device and are
+    // ignored just as they are by wipefs.
+    fn from_result(result: ProbeResult) -> Option<Self> {
+        if let Some(sig_type) = result.pt_type {
+            Some(Signature {
+                kind: SignatureKind::PartitionTable,
+                sig_type,
+                offset: result.ptmagic_offset?,
+                magic: result.ptmagic?,
+                usage: None,
+                uuid: result.pt_uuid,
+                label: None,
+            })
+        } else {
+            let uuid = result.uuid_string();
+            Some(Signature {
+                kind: SignatureKind::Superblock,
+                sig_type: result.fs_type?,
+                offset: result.sbmagic_offset?,
+                magic: result.sbmagic?,
+                usage: result.usage,
+                uuid,
+                label: result.label,
+            })
+        }
+    }
+}
+
+/// The original bytes of a signature saved before it is wiped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureBackup {
+    /// Offset of the bytes in bytes from the start of the device
+    pub offset: u64,
+    /// The bytes that were overwritten by the wipe
+    pub data: Vec<u8>,
+}
+
+impl SignatureBackup {
+    /// Write the backup to a stream. Each backup is written as its offset and
+    /// the length of its data as little endian 64 bit integers followed by the
+    /// data itself so multiple backups can be written to the same stream.
+    pub fn write_to<W>(&self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.offset.to_le_bytes())?;
+        writer.write_all(&(self.data.len() as u64).to_le_bytes())?;
+        writer.write_all(&self.data)?;
+        Ok(())
+    }
+
+    /// Read a backup written by `write_to` from a stream or return `None` if
+    /// the end of the stream has been reached.
+    pub fn read_from<R>(reader: &mut R) -> Result<Option<Self>>
+    where
+        R: Read,
+    {
+        let mut offset = [0u8; 8];
+        match reader.read_exact(&mut offset) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(BlkidErr::IO(e)),
+        }
+        let mut len = [0u8; 8];
+        reader.read_exact(&mut len)?;
+        let mut data = Vec::new();
+        reader
+            .take(u64::from_le_bytes(len))
+            .read_to_end(&mut data)?;
+        if data.len() as u64 != u64::from_le_bytes(len) {
+            return Err(BlkidErr::IO(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(Some(SignatureBackup {
+            offset: u64::from_le_bytes(offset),
+            data,
+        }))
+    }
+
+    /// Read all backups written by `write_to` from a stream.
+    pub fn read_all<R>(reader: &mut R) -> Result<Vec<Self>>
+    where
+        R: Read,
+    {
+        let mut backups = Vec::new();
+        while let Some(backup) = Self::read_from(reader)? {
+            backups.push(backup);
+        }
+        Ok(backups)
+    }
+
+    /// Save the backup to its own file in `dir` using the same naming scheme
+    /// as `wipefs --backup`, `wipefs-<device_name>-<offset>.bak`, and return
+    /// the path of the file.
+    pub fn save_to_dir(&self, dir: &Path, device_name: &str) -> Result<PathBuf> {
+        let path = dir.join(format!("wipefs-{}-{:#010x}.bak", device_name, self.offset));
+        File::create(&path)?.write_all(&self.data)?;
+        Ok(path)
+    }
+
+    /// Load a backup from a file created by `save_to_dir` or
+    /// `wipefs --backup`. The offset is parsed from the file name.
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let offset = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".bak"))
+            .and_then(|name| name.rsplit_once("-0x"))
+            .and_then(|(_, offset)| u64::from_str_radix(offset, 16).ok())
+            .ok_or_else(|| {
+                BlkidErr::Other(format!(
+                    "{} is not named like a wipefs backup file",
+                    path.display()
+                ))
+            })?;
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Ok(SignatureBackup { offset, data })
+    }
+}
+
+impl From<&Signature> for SignatureBackup {
+    fn from(signature: &Signature) -> Self {
+        SignatureBackup {
+            offset: signature.offset,
+            data: signature.magic.clone(),
+        }
+    }
+}
+
+/// Enumerates and wipes all superblock and partition table signatures on a
+/// device in the same way as `wipefs`.
+///
+/// Wiping a partition table does not notify the kernel of the change.
+pub struct Signatures {
+    probe: BlkidProbe,
+    // The probe must be dropped before the file it uses.
+    file: File,
+}
+
+impl Signatures {
+    /// Open a device read-only. Signatures can be listed and wiped only in
+    /// dry run mode.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_file(File::open(path)?)
+    }
+
+    /// Open a device for wiping. Block devices are opened exclusively so
+    /// this will fail for devices that are mounted or otherwise in use.
+    pub fn open_writable(path: &Path) -> Result<Self> {
+        Self::from_file(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_EXCL)
+                .open(path)?,
+        )
+    }
+
+    fn from_file(file: File) -> Result<Self> {
+        let mut probe = BlkidProbe::new()?;
+        probe.set_device(file.as_raw_fd(), 0, 0)?;
+        probe.enable_superblocks(true)?;
+        probe.set_superblock_flags(BlkidSublksFlags::new(vec![
+            BlkidSublks::Magic,
+            BlkidSublks::Type,
+            BlkidSublks::Usage,
+            BlkidSublks::Label,
+            BlkidSublks::Uuid,
+            BlkidSublks::Badcsum,
+        ]))?;
+        probe.enable_partitions(true)?;
+        probe.set_partition_flags(BlkidPartsFlags::new(vec![
+            BlkidParts::Magic,
+            BlkidParts::ForceGpt,
+        ]))?;
+        Ok(Signatures { probe, file })
+    }
+
+    /// List all signatures on the device including backup copies such as the
+    /// secondary GPT header.
+    pub fn list(&mut self) -> Result<Vec<Signature>> {
+        self.wipe(|_| true, true)
+    }
+
+    /// Wipe all signatures on the device and return the signatures that were
+    /// wiped. If `dry_run` is `true`, the device is not modified and the
+    /// signatures that would have been wiped are returned.
+    pub fn wipe_all(&mut self, dry_run: bool) -> Result<Vec<Signature>> {
+        self.wipe(|_| true, dry_run)
+    }
+
+    /// Wipe all signatures for which `filter` returns `true` and return the
+    /// signatures that were wiped. If `dry_run` is `true`, the device is not
+    /// modified and the signatures that would have been wiped are returned.
+    pub fn wipe<F>(&mut self, filter: F, dry_run: bool) -> Result<Vec<Signature>>
+    where
+        F: FnMut(&Signature) -> bool,
+    {
+        self.wipe_shared(filter, dry_run, None::<fn(&SignatureBackup) -> Result<()>>)
+    }
+
+    /// Wipe all signatures for which `filter` returns `true` and return the
+    /// signatures that were wiped. `backup` is called with the original bytes
+    /// of each signature before it is wiped and the signature is left intact
+    /// if it returns an error.
+    ///
+    /// To save all backups to a single stream, use
+    /// `|b| b.write_to(&mut writer)`. To save each backup to its own file, use
+    /// `|b| b.save_to_dir(dir, device_name).map(|_| ())`.
+    pub fn wipe_with_backup<F, B>(&mut self, filter: F, backup: B) -> Result<Vec<Signature>>
+    where
+        F: FnMut(&Signature) -> bool,
+        B: FnMut(&SignatureBackup) -> Result<()>,
+    {
+        self.wipe_shared(filter, false, Some(backup))
+    }
+
+    fn wipe_shared<F, B>(
+        &mut self,
+        mut filter: F,
+        dry_run: bool,
+        mut backup: Option<B>,
+    ) -> Result<Vec<Signature>>
+    where
+        F: FnMut(&Signature) -> bool,
+        B: FnMut(&SignatureBackup) -> Result<()>,
+    {
+        self.probe.reset_buffers()?;
+        self.probe.reset();
+
+        let mut signatures = Vec::new();
+        while self.probe.do_probe()? == BlkidProbeRet::Success {
+            let signature = match Signature::from_result(self.probe.get_result()?) {
+                Some(signature) => signature,
+                None => continue,
+            };
+            // A dry run wipe hides the signature from subsequent probing so
+            // that every signature is reported exactly once.
+            if filter(&signature) {
+                if let Some(ref mut backup) = backup {
+                    backup(&SignatureBackup::from(&signature))?;
+                }
+                self.probe.do_wipe(dry_run)?;
+                signatures.push(signature);
+            } else {
+                self.probe.do_wipe(true)?;
+            }
+        }
+        Ok(signatures)
+    }
+
+    /// Write the bytes saved in `backups` back to the device. The device must
+    /// have been opened with `open_writable`.
+    pub fn restore(&mut self, backups: &[SignatureBackup]) -> Result<()> {
+        for backup in backups {
+            self.file.write_all_at(&backup.data, backup.offset)?;
+        }
+        self.file.sync_all()?;
+        self.probe.reset_buffers()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use crate::test_utils::{TempDir, ext2_swap_image};
+
+    // Write an ext2+swap image into a temporary directory.
+    fn image(name: &str) -> (TempDir, PathBuf, Vec<u8>) {
+        let dir = TempDir::new(name);
+        let data = ext2_swap_image();
+        let path = dir.path().join("disk.img");
+        fs::write(&path, &data).unwrap();
+        (dir, path, data)
+    }
+
+    fn types(signatures: &[Signature]) -> Vec<(&str, u64)> {
+        let mut types = signatures
+            .iter()
+            .map(|s| (s.sig_type.as_str(), s.offset))
+            .collect::<Vec<_>>();
+        types.sort();
+        types
+    }
+
+    #[test]
+    fn test_list_and_dry_run() {
+        let (_dir, path, data) = image("signatures-dry-run");
+        let mut signatures = Signatures::open(&path).unwrap();
+
+        let listed = signatures.list().unwrap();
+        assert_eq!(types(&listed), vec![("ext2", 0x438), ("swap", 0xff6)]);
+        let ext2 = listed.iter().find(|s| s.sig_type == "ext2").unwrap();
+        assert_eq!(ext2.kind, SignatureKind::Superblock);
+        assert_eq!(ext2.magic, vec![0x53, 0xef]);
+        assert_eq!(ext2.usage, Some(BlkidUsageFlag::Filesystem));
+
+        assert_eq!(
+            types(&signatures.wipe(|s| s.sig_type == "swap", true).unwrap()),
+            vec![("swap", 0xff6)]
+        );
+        assert_eq!(types(&signatures.wipe_all(true).unwrap()), types(&listed));
+        // Repeated dry runs report the same signatures and leave the image
+        // intact.
+        assert_eq!(types(&signatures.list().unwrap()), types(&listed));
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_wipe_and_restore() {
+        let (_dir, path, data) = image("signatures-restore");
+        let mut signatures = Signatures::open_writable(&path).unwrap();
+
+        let mut backups = Vec::new();
+        let wiped = signatures
+            .wipe_with_backup(
+                |_| true,
+                |b| {
+                    backups.push(b.clone());
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert_eq!(types(&wiped), vec![("ext2", 0x438), ("swap", 0xff6)]);
+        assert!(signatures.list().unwrap().is_empty());
+        let image = fs::read(&path).unwrap();
+        assert_eq!(&image[0x438..0x43a], &[0, 0]);
+        assert_eq!(&image[0xff6..0x1000], &[0; 10]);
+
+        signatures.restore(&backups).unwrap();
+        assert_eq!(types(&signatures.list().unwrap()), types(&wiped));
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_backup_file_round_trip() {
+        let (dir, path, _) = image("signatures-backup-file");
+        let dir = dir.path();
+        let backup = SignatureBackup {
+            offset: 0x438,
+            data: vec![0x53, 0xef],
+        };
+        let file = backup.save_to_dir(dir, "sda").unwrap();
+        assert_eq!(file, dir.join("wipefs-sda-0x00000438.bak"));
+        assert_eq!(SignatureBackup::load_from_file(&file).unwrap(), backup);
+        // Device names may contain dashes and offsets may exceed 32 bits.
+        let file = dir.join("wipefs-dm-0-0x100000000.bak");
+        fs::write(&file, b"EFI PART").unwrap();
+        assert_eq!(
+            SignatureBackup::load_from_file(&file).unwrap(),
+            SignatureBackup {
+                offset: 0x1_0000_0000,
+                data: b"EFI PART".to_vec(),
+            }
+        );
+        assert!(SignatureBackup::load_from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_backup_stream_round_trip() {
+        let backups = vec![
+            SignatureBackup {
+                offset: 0x438,
+                data: vec![0x53, 0xef],
+            },
+            SignatureBackup {
+                offset: 0x200,
+                data: b"EFI PART".to_vec(),
+            },
+        ];
+        let mut stream = Vec::new();
+        for backup in &backups {
+            backup.write_to(&mut stream).unwrap();
+        }
+        assert_eq!(
+            SignatureBackup::read_all(&mut stream.as_slice()).unwrap(),
+            backups
+        );
+        assert!(SignatureBackup::read_all(&mut &stream[..stream.len() - 1]).is_err());
+    }
+}
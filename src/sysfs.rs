@@ -0,0 +1,563 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Result,
+    devno::BlkidDevno,
+    err::BlkidErr,
+    probe::BlkidProbe,
+    utils::{BlkidSectors, LIBBLKID_SECTOR_SIZE},
+};
+
+const SYSFS_ROOT: &str = "/sys";
+const DEV_ROOT: &str = "/dev";
+
+// Read a sysfs attribute without the trailing newline.
+fn read_attr(dir: &Path, attr: &str) -> Result<String> {
+    Ok(fs::read_to_string(dir.join(attr))?.trim_end().to_string())
+}
+
+// Parse the contents of a sysfs dev attribute of the form major:minor.
+fn parse_devno(dev: &str) -> Option<BlkidDevno> {
+    let (major, minor) = dev.split_once(':')?;
+    Some(BlkidDevno::from_device_numbers(
+        major.parse().ok()?,
+        minor.parse().ok()?,
+    ))
+}
+
+// Get the device name from the DEVNAME entry of the uevent attribute, which
+// includes subdirectories of /dev, falling back to the sysfs directory name.
+fn devname(dir: &Path) -> Option<String> {
+    fs::read_to_string(dir.join("uevent"))
+        .ok()
+        .and_then(|uevent| {
+            uevent
+                .lines()
+                .find_map(|line| line.strip_prefix("DEVNAME=").map(|s| s.to_string()))
+        })
+        .or_else(|| {
+            dir.file_name()
+                .and_then(|name| name.to_str())
+                .map(|s| s.to_string())
+        })
+}
+
+// Read an optional boolean sysfs attribute such as ro or removable.
+fn read_flag(dir: &Path, attr: &str) -> Option<bool> {
+    read_attr(dir, attr).ok().map(|value| value == "1")
+}
+
+/// A block device listed in sysfs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockDevice {
+    /// Device number
+    pub devno: BlkidDevno,
+    /// Kernel name of the device such as `sda1`
+    pub name: String,
+    /// Path of the device node
+    pub devnode: PathBuf,
+    /// Partition number if the device is a partition
+    pub partno: Option<libc::c_uint>,
+    /// Kernel name of the whole disk if the device is a partition
+    pub parent: Option<String>,
+    /// Size of the device in 512-byte sectors
+    pub size: BlkidSectors,
+    /// Whether the device or its whole disk is removable
+    pub removable: bool,
+    /// Whether the device is read-only
+    pub read_only: bool,
+    /// Disk sequence number of the device or its whole disk if reported by
+    /// the kernel
+    pub diskseq: Option<u64>,
+}
+
+impl BlockDevice {
+    fn from_dir(dir: &Path, dev_root: &Path) -> Result<Self> {
+        let invalid = |attr: &str| {
+            BlkidErr::Other(format!("Invalid {} attribute in {}", attr, dir.display()))
+        };
+        let partno = match read_attr(dir, "partition") {
+            Ok(partno) => Some(partno.parse().map_err(|_| invalid("partition"))?),
+            Err(_) => None,
+        };
+        // Attributes such as removable and diskseq only exist for whole disks.
+        let disk = match partno {
+            Some(_) => dir.parent().ok_or_else(|| invalid("partition"))?,
+            None => dir,
+        };
+        let name = dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.to_string())
+            .ok_or_else(|| BlkidErr::Other(format!("Invalid device name {}", dir.display())))?;
+        Ok(BlockDevice {
+            devno: parse_devno(&read_attr(dir, "dev")?).ok_or_else(|| invalid("dev"))?,
+            devnode: dev_root.join(devname(dir).unwrap_or_else(|| name.clone())),
+            parent: partno.and_then(|_| {
+                disk.file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| name.to_string())
+            }),
+            name,
+            partno,
+            size: BlkidSectors::new(
+                read_attr(dir, "size")?
+                    .parse()
+                    .map_err(|_| invalid("size"))?,
+                LIBBLKID_SECTOR_SIZE,
+            ),
+            removable: read_flag(disk, "removable").unwrap_or(false),
+            read_only: read_flag(dir, "ro").unwrap_or(false),
+            diskseq: read_attr(disk, "diskseq")
+                .ok()
+                .and_then(|diskseq| diskseq.parse().ok()),
+        })
+    }
+
+    /// Whether the device is a partition.
+    pub fn is_partition(&self) -> bool {
+        self.partno.is_some()
+    }
+
+    /// Open a probe for the device node.
+    pub fn probe(&self) -> Result<BlkidProbe> {
+        BlkidProbe::new_from_filename(&self.devnode)
+    }
+}
+
+/// Access to block device information in sysfs.
+///
+/// Unlike `BlkidCache`, this lists all block devices known to the kernel
+/// without reading or writing a cache file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sysfs {
+    root: PathBuf,
+    dev_root: PathBuf,
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Sysfs::new(Path::new(SYSFS_ROOT), Path::new(DEV_ROOT))
+    }
+}
+
+impl Sysfs {
+    /// Use the sysfs tree mounted at `root` and device nodes in `dev_root`
+    /// instead of `/sys` and `/dev`.
+    pub fn new(root: &Path, dev_root: &Path) -> Self {
+        Sysfs {
+            root: root.to_path_buf(),
+            dev_root: dev_root.to_path_buf(),
+        }
+    }
+
+    // Get the sysfs directory of a block device.
+    fn device_dir(&self, devno: BlkidDevno) -> Result<PathBuf> {
+        Ok(fs::canonicalize(
+            self.root
+                .join("dev/block")
+                .join(format!("{}:{}", devno.major(), devno.minor())),
+        )?)
+    }
+
+    /// List all block devices including partitions sorted by name. Devices
+    /// that disappear while they are listed are left out.
+    pub fn block_devices(&self) -> Result<Vec<BlockDevice>> {
+        let mut devices = fs::read_dir(self.root.join("class/block"))?
+            .map(|dir_entry| {
+                BlockDevice::from_dir(&fs::canonicalize(dir_entry?.path())?, &self.dev_root)
+            })
+            // Skip devices that are removed while the directory is read.
+            .filter(|device| {
+                !matches!(device, Err(BlkidErr::IO(e)) if e.kind() == io::ErrorKind::NotFound)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(devices)
+    }
+
+    /// Get the block device with the given device number.
+    pub fn block_device(&self, devno: BlkidDevno) -> Result<BlockDevice> {
+        BlockDevice::from_dir(&self.device_dir(devno)?, &self.dev_root)
+    }
+
+    /// Build the graph of stacking relationships between all block devices.
+    pub fn device_graph(&self) -> Result<DeviceGraph> {
+        DeviceGraph::new(self)
+    }
+
+    /// Get the device node path and device number of the partition with the
+    /// given partition number on the whole disk of the device `devno`.
+    pub fn partition_device(
+        &self,
+        devno: BlkidDevno,
+        partno: libc::c_uint,
+    ) -> Result<(PathBuf, BlkidDevno)> {
+        let mut disk = self.device_dir(devno)?;
+        // The sysfs directory of a partition is a subdirectory of its whole disk.
+        if disk.join("partition").exists() {
+            disk.pop();
+        }
+        for dir_entry in fs::read_dir(&disk)? {
+            let dir = dir_entry?.path();
+            match read_attr(&dir, "partition") {
+                Ok(num) if num.parse() == Ok(partno) => (),
+                _ => continue,
+            }
+            let part_devno = parse_devno(&read_attr(&dir, "dev")?).ok_or_else(|| {
+                BlkidErr::Other(format!("Invalid device number in {}", dir.display()))
+            })?;
+            let name = devname(&dir)
+                .ok_or_else(|| BlkidErr::Other(format!("No device name for {}", dir.display())))?;
+            return Ok((self.dev_root.join(name), part_devno));
+        }
+        Err(BlkidErr::Other(format!(
+            "No partition {} found for device {}:{}",
+            partno,
+            devno.major(),
+            devno.minor()
+        )))
+    }
+}
+
+/// A graph of the stacking relationships between block devices, such as
+/// device-mapper or MD RAID devices built on top of partitions.
+///
+/// Devices are identified by device number as returned by
+/// `BlkidProbe::get_devno` or `BlkidProbe::get_partition_device`.
+#[derive(Clone, Debug)]
+pub struct DeviceGraph {
+    devices: HashMap<BlkidDevno, BlockDevice>,
+    holders: HashMap<BlkidDevno, Vec<BlkidDevno>>,
+    slaves: HashMap<BlkidDevno, Vec<BlkidDevno>>,
+}
+
+impl DeviceGraph {
+    fn new(sysfs: &Sysfs) -> Result<Self> {
+        let devices = sysfs.block_devices()?;
+        let by_name = devices
+            .iter()
+            .map(|device| (device.name.as_str(), device.devno))
+            .collect::<HashMap<_, _>>();
+        let links = |device: &BlockDevice, dir: &str| -> Result<Vec<BlkidDevno>> {
+            let path = sysfs.root.join("class/block").join(&device.name).join(dir);
+            if !path.exists() {
+                return Ok(Vec::new());
+            }
+            let mut links = fs::read_dir(path)?
+                .filter_map(|dir_entry| match dir_entry {
+                    Ok(dir_entry) => dir_entry
+                        .file_name()
+                        .to_str()
+                        .and_then(|name| by_name.get(name).copied())
+                        .map(Ok),
+                    Err(e) => Some(Err(BlkidErr::IO(e))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            links.sort_by_key(|devno| (devno.major(), devno.minor()));
+            Ok(links)
+        };
+
+        let mut holders = HashMap::new();
+        let mut slaves = HashMap::new();
+        for device in &devices {
+            holders.insert(device.devno, links(device, "holders")?);
+            slaves.insert(device.devno, links(device, "slaves")?);
+        }
+        Ok(DeviceGraph {
+            devices: devices
+                .into_iter()
+                .map(|device| (device.devno, device))
+                .collect(),
+            holders,
+            slaves,
+        })
+    }
+
+    /// Get the block device with the given device number.
+    pub fn device(&self, devno: BlkidDevno) -> Option<&BlockDevice> {
+        self.devices.get(&devno)
+    }
+
+    /// Get the block device for a device node path such as
+    /// `/dev/mapper/name`.
+    pub fn device_by_path(&self, path: &Path) -> Result<&BlockDevice> {
+        let devno = BlkidDevno::new(fs::metadata(path)?.rdev() as libc::dev_t);
+        self.device(devno).ok_or_else(|| {
+            BlkidErr::Other(format!("{} is not a known block device", path.display()))
+        })
+    }
+
+    /// Get the partitions of a whole disk.
+    pub fn partitions(&self, devno: BlkidDevno) -> Vec<BlkidDevno> {
+        let name = match self.device(devno) {
+            Some(device) => &device.name,
+            None => return Vec::new(),
+        };
+        let mut partitions = self
+            .devices
+            .values()
+            .filter(|device| device.parent.as_ref() == Some(name))
+            .map(|device| device.devno)
+            .collect::<Vec<_>>();
+        partitions.sort_by_key(|devno| (devno.major(), devno.minor()));
+        partitions
+    }
+
+    /// Get the devices directly stacked on top of a device.
+    pub fn holders(&self, devno: BlkidDevno) -> &[BlkidDevno] {
+        self.holders
+            .get(&devno)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Get the devices a device is directly stacked on.
+    pub fn slaves(&self, devno: BlkidDevno) -> &[BlkidDevno] {
+        self.slaves.get(&devno).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    // Walk the graph breadth first from the start devices and return every
+    // other device reached.
+    fn walk(
+        &self,
+        start: &[BlkidDevno],
+        edges: &HashMap<BlkidDevno, Vec<BlkidDevno>>,
+    ) -> Vec<BlkidDevno> {
+        let mut seen = start.iter().copied().collect::<HashSet<_>>();
+        let mut queue = start.iter().copied().collect::<VecDeque<_>>();
+        let mut reached = Vec::new();
+        while let Some(next) = queue.pop_front() {
+            for &edge in edges.get(&next).map(|v| v.as_slice()).unwrap_or(&[]) {
+                if seen.insert(edge) {
+                    reached.push(edge);
+                    queue.push_back(edge);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Get all devices stacked directly or indirectly on top of a device. For
+    /// a whole disk this includes the devices stacked on its partitions.
+    pub fn all_holders(&self, devno: BlkidDevno) -> Vec<BlkidDevno> {
+        let mut start = vec![devno];
+        start.extend(self.partitions(devno));
+        self.walk(&start, &self.holders)
+    }
+
+    /// Get the devices at the bottom of the stack below a device, which are
+    /// the devices it depends on that are not stacked on anything else. A
+    /// device that is not stacked on anything is its own leaf.
+    ///
+    /// Leaves may be partitions; use `physical_devices` to get the whole
+    /// disks they are on.
+    pub fn leaves(&self, devno: BlkidDevno) -> Vec<BlkidDevno> {
+        if self.slaves(devno).is_empty() {
+            return vec![devno];
+        }
+        self.walk(&[devno], &self.slaves)
+            .into_iter()
+            .filter(|slave| self.slaves(*slave).is_empty())
+            .collect()
+    }
+
+    /// Get the whole disks at the bottom of the stack below a device. This is
+    /// the same as `leaves` with each partition replaced by its whole disk.
+    pub fn physical_devices(&self, devno: BlkidDevno) -> Vec<BlkidDevno> {
+        let mut disks = Vec::new();
+        for leaf in self.leaves(devno) {
+            let disk = self
+                .device(leaf)
+                .and_then(|device| device.parent.as_ref())
+                .and_then(|parent| self.devices.values().find(|device| &device.name == parent))
+                .map(|device| device.devno)
+                .unwrap_or(leaf);
+            if !disks.contains(&disk) {
+                disks.push(disk);
+            }
+        }
+        disks
+    }
+}
+
+impl BlkidDevno {
+    /// Get the device node path and device number of the kernel partition
+    /// device with the given partition number on the whole disk of this
+    /// device. The partition is looked up in sysfs.
+    pub fn to_partition(&self, partno: libc::c_uint) -> Result<(PathBuf, BlkidDevno)> {
+        Sysfs::default().partition_device(*self, partno)
+    }
+}
+
+impl BlkidProbe {
+    /// Get the device node path and device number of the kernel partition
+    /// device for a partition with the given number found by probing a whole
+    /// disk, as returned by `BlkidPartition::get_partno`.
+    ///
+    /// The probe must be associated with a block device and the kernel must
+    /// have read the partition table.
+    pub fn get_partition_device(&self, partno: libc::c_uint) -> Result<(PathBuf, BlkidDevno)> {
+        let devno = self.get_wholedisk_devno();
+        if devno.as_dev_t() == 0 {
+            return Err(BlkidErr::Other(
+                "Probe is not associated with a block device".to_string(),
+            ));
+        }
+        devno.to_partition(partno)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::os::unix::fs::symlink;
+
+    use crate::test_utils::TempDir;
+
+    // Create a fake sysfs tree with a removable disk sda with two partitions.
+    fn fake_sysfs(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        let root = dir.path();
+        let disk = root.join("devices/virtual/block/sda");
+        fs::create_dir_all(&disk).unwrap();
+        fs::write(disk.join("dev"), "8:0\n").unwrap();
+        fs::write(disk.join("size"), "2048\n").unwrap();
+        fs::write(disk.join("removable"), "1\n").unwrap();
+        fs::write(disk.join("ro"), "0\n").unwrap();
+        fs::write(disk.join("diskseq"), "7\n").unwrap();
+        for (name, partno, dev) in [("sda1", "1", "8:1"), ("sda2", "2", "8:2")] {
+            fs::create_dir_all(disk.join(name)).unwrap();
+            fs::write(disk.join(name).join("partition"), format!("{partno}\n")).unwrap();
+            fs::write(disk.join(name).join("dev"), format!("{dev}\n")).unwrap();
+            fs::write(disk.join(name).join("size"), "1024\n").unwrap();
+            fs::write(disk.join(name).join("ro"), "0\n").unwrap();
+        }
+        fs::write(disk.join("sda2/ro"), "1\n").unwrap();
+        fs::write(disk.join("sda2/uevent"), "MAJOR=8\nDEVNAME=disk/sda2\n").unwrap();
+        fs::create_dir_all(root.join("dev/block")).unwrap();
+        fs::create_dir_all(root.join("class/block")).unwrap();
+        for (name, dev, path) in [
+            ("sda", "8:0", disk.clone()),
+            ("sda1", "8:1", disk.join("sda1")),
+            ("sda2", "8:2", disk.join("sda2")),
+        ] {
+            symlink(&path, root.join("dev/block").join(dev)).unwrap();
+            symlink(&path, root.join("class/block").join(name)).unwrap();
+        }
+        // dm-0 is stacked on both partitions and dm-1 on dm-0.
+        let virt = root.join("devices/virtual/block");
+        for (name, dev, holders, slaves) in [
+            ("dm-0", "253:0", vec!["dm-1"], vec!["sda1", "sda2"]),
+            ("dm-1", "253:1", vec![], vec!["dm-0"]),
+        ] {
+            fs::create_dir_all(virt.join(name).join("holders")).unwrap();
+            fs::create_dir_all(virt.join(name).join("slaves")).unwrap();
+            fs::write(virt.join(name).join("dev"), format!("{dev}\n")).unwrap();
+            fs::write(virt.join(name).join("size"), "1024\n").unwrap();
+            for holder in holders {
+                symlink(
+                    virt.join(holder),
+                    virt.join(name).join("holders").join(holder),
+                )
+                .unwrap();
+            }
+            for slave in slaves {
+                symlink(virt.join(slave), virt.join(name).join("slaves").join(slave)).unwrap();
+            }
+            symlink(virt.join(name), root.join("dev/block").join(dev)).unwrap();
+            symlink(virt.join(name), root.join("class/block").join(name)).unwrap();
+        }
+        for part in ["sda1", "sda2"] {
+            fs::create_dir_all(disk.join(part).join("holders")).unwrap();
+            symlink(virt.join("dm-0"), disk.join(part).join("holders/dm-0")).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_block_devices() {
+        let dir = fake_sysfs("block-devices");
+        let root = dir.path();
+        // A device that was removed after the directory was read.
+        symlink(
+            root.join("devices/virtual/block/sdb"),
+            root.join("class/block/sdb"),
+        )
+        .unwrap();
+        let devices = Sysfs::new(root, Path::new("/dev")).block_devices().unwrap();
+        assert_eq!(devices.len(), 5);
+        assert_eq!(devices[2].name, "sda");
+        assert!(!devices[2].is_partition());
+        assert_eq!(devices[2].size, BlkidSectors::new(2048, 512));
+        assert_eq!(devices[2].diskseq, Some(7));
+        assert_eq!(devices[4].devno, BlkidDevno::from_device_numbers(8, 2));
+        assert_eq!(devices[4].devnode, PathBuf::from("/dev/disk/sda2"));
+        assert_eq!(devices[4].partno, Some(2));
+        assert_eq!(devices[4].parent.as_deref(), Some("sda"));
+        assert!(devices[4].removable);
+        assert!(devices[4].read_only);
+        assert_eq!(devices[4].diskseq, Some(7));
+    }
+
+    #[test]
+    fn test_partition_device() {
+        let dir = fake_sysfs("partition-device");
+        let root = dir.path();
+        let sysfs = Sysfs::new(root, Path::new("/dev"));
+        let whole = BlkidDevno::from_device_numbers(8, 0);
+        assert_eq!(
+            sysfs.partition_device(whole, 1).unwrap(),
+            (
+                PathBuf::from("/dev/sda1"),
+                BlkidDevno::from_device_numbers(8, 1)
+            )
+        );
+        assert_eq!(
+            sysfs
+                .partition_device(BlkidDevno::from_device_numbers(8, 1), 2)
+                .unwrap(),
+            (
+                PathBuf::from("/dev/disk/sda2"),
+                BlkidDevno::from_device_numbers(8, 2)
+            )
+        );
+        assert!(sysfs.partition_device(whole, 3).is_err());
+    }
+
+    #[test]
+    fn test_device_graph() {
+        let dir = fake_sysfs("device-graph");
+        let root = dir.path();
+        let graph = Sysfs::new(root, Path::new("/dev")).device_graph().unwrap();
+        let devno = BlkidDevno::from_device_numbers;
+        assert_eq!(
+            graph.partitions(devno(8, 0)),
+            vec![devno(8, 1), devno(8, 2)]
+        );
+        assert_eq!(graph.holders(devno(8, 1)), &[devno(253, 0)]);
+        assert_eq!(
+            graph.all_holders(devno(8, 1)),
+            vec![devno(253, 0), devno(253, 1)]
+        );
+        assert_eq!(graph.slaves(devno(253, 0)), &[devno(8, 1), devno(8, 2)]);
+        assert_eq!(graph.leaves(devno(253, 1)), vec![devno(8, 1), devno(8, 2)]);
+        assert_eq!(graph.leaves(devno(8, 0)), vec![devno(8, 0)]);
+        assert_eq!(
+            graph.all_holders(devno(8, 0)),
+            vec![devno(253, 0), devno(253, 1)]
+        );
+        assert_eq!(graph.physical_devices(devno(253, 1)), vec![devno(8, 0)]);
+        assert_eq!(graph.physical_devices(devno(8, 2)), vec![devno(8, 0)]);
+    }
+}
@@ -0,0 +1,552 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{BTreeMap, btree_map},
+    fmt::{self, Display},
+    ops::Range,
+};
+
+use crate::{
+    Result,
+    attributes::PartitionFlags,
+    partition::{BlkidPartition, BlkidPartlist, BlkidParttable},
+    parttype::PartitionType,
+    probe::BlkidProbe,
+    utils::{BlkidBytes, BlkidSectors, LIBBLKID_SECTOR_SIZE},
+};
+
+// Size of a GPT partition entry array with the standard 128 entries.
+const GPT_ENTRIES_SIZE: libblkid_rs_sys::blkid_loff_t = 128 * 128;
+
+// Signed integer div_ceil is not yet stable; all values here are non-negative.
+fn div_ceil(
+    a: libblkid_rs_sys::blkid_loff_t,
+    b: libblkid_rs_sys::blkid_loff_t,
+) -> libblkid_rs_sys::blkid_loff_t {
+    (a + b - 1) / b
+}
+
+fn sectors(num: libblkid_rs_sys::blkid_loff_t) -> BlkidSectors {
+    BlkidSectors::new(num, LIBBLKID_SECTOR_SIZE)
+}
+
+/// An owned copy of a partition from a partition list.
+///
+/// The start and size are in units of 512-byte sectors as reported by
+/// libblkid regardless of the sector size of the device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionEntry {
+    /// Partition number
+    pub partno: libc::c_uint,
+    /// Start of the partition
+    pub start: BlkidSectors,
+    /// Size of the partition
+    pub size: BlkidSectors,
+    /// Partition ID; a UUID for GPT and the disk ID followed by the partition
+    /// number for DOS
+    pub uuid: Option<String>,
+    /// Partition name
+    pub name: Option<String>,
+    /// Numeric partition type as used by DOS partition tables
+    pub type_code: libc::c_int,
+    /// String partition type such as the type GUID of a GPT partition
+    pub type_string: Option<String>,
+    /// Typed partition type for GPT and DOS partition tables
+    pub partition_type: Option<PartitionType>,
+    /// Partition flags or GPT attributes
+    pub flags: PartitionFlags,
+    /// Whether this is a DOS extended partition
+    pub is_extended: bool,
+    /// Whether this is a logical partition; libblkid also reports partitions
+    /// of nested partition tables as logical
+    pub is_logical: bool,
+    /// Whether this is a primary partition
+    pub is_primary: bool,
+    /// Number of the partition containing the nested partition table this
+    /// partition belongs to
+    pub parent: Option<libc::c_uint>,
+}
+
+impl PartitionEntry {
+    fn from_partition(partition: &BlkidPartition<'_>) -> Result<Self> {
+        let table = partition.get_table()?;
+        let parent = match table.get_parent() {
+            Some(parent) => Some(parent.get_partno()?),
+            None => None,
+        };
+        let pt_type = table.get_type()?;
+        let type_code = partition.get_type();
+        let type_string = partition.get_type_string().ok();
+        Ok(PartitionEntry {
+            partno: partition.get_partno()?,
+            start: partition.get_start(),
+            size: partition.get_size(),
+            uuid: partition.get_id()?,
+            name: partition.get_name()?,
+            partition_type: PartitionType::from_raw(&pt_type, type_code, type_string.as_deref()),
+            type_code,
+            type_string,
+            flags: PartitionFlags::from_raw(&pt_type, partition.get_flags()),
+            is_extended: partition.is_extended(),
+            is_logical: partition.is_logical(),
+            is_primary: partition.is_primary(),
+            parent,
+        })
+    }
+
+    /// Get the first sector after the end of the partition.
+    pub fn end(&self) -> BlkidSectors {
+        sectors(*self.start.as_ref() + *self.size.as_ref())
+    }
+
+    // Whether this partition is expected to contain the other one, as an
+    // extended partition contains logical partitions and a partition contains
+    // a nested partition table.
+    fn contains_child(&self, other: &PartitionEntry) -> bool {
+        (self.is_extended && other.is_logical) || other.parent == Some(self.partno)
+    }
+}
+
+/// An owned snapshot of the partition table found by a probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionTable {
+    /// Partition table type such as `gpt` or `dos`
+    pub pt_type: String,
+    /// Partition table ID
+    pub id: Option<String>,
+    /// Offset of the partition table
+    pub offset: BlkidBytes,
+    /// Size of the probed device
+    pub device_size: BlkidBytes,
+    /// Logical sector size of the probed device
+    pub sector_size: libc::c_uint,
+    /// All partitions sorted by start and partition number, including logical
+    /// partitions and partitions of nested partition tables
+    pub partitions: Vec<PartitionEntry>,
+}
+
+impl PartitionTable {
+    /// Build a partition table from a probe for which partition probing has
+    /// been enabled and performed.
+    pub fn from_probe(probe: &mut BlkidProbe) -> Result<Self> {
+        let device_size = BlkidBytes::new(probe.get_size());
+        let sector_size = probe.get_sector_size();
+        let mut list = probe.get_partitions()?;
+        let table = list.get_table()?;
+        let mut partitions = (0..list.number_of_partitions()?)
+            .map(|i| PartitionEntry::from_partition(&list.get_partition(i)?))
+            .collect::<Result<Vec<_>>>()?;
+        partitions.sort_by(|a, b| a.start.cmp(&b.start).then(a.partno.cmp(&b.partno)));
+        Ok(PartitionTable {
+            pt_type: table.get_type()?,
+            id: table.get_id().ok(),
+            offset: table.get_offset()?,
+            device_size,
+            sector_size,
+            partitions,
+        })
+    }
+
+    /// Get the area of the device that can hold partitions. This excludes the
+    /// first sector and, for GPT, the primary and backup headers and entry
+    /// arrays assuming the standard 128 entries.
+    pub fn usable_range(&self) -> Range<BlkidSectors> {
+        let sector_size = libblkid_rs_sys::blkid_loff_t::from(self.sector_size);
+        let size = *self.device_size.as_ref();
+        let (start, end) = if self.pt_type == "gpt" {
+            let entries = div_ceil(GPT_ENTRIES_SIZE, sector_size) * sector_size;
+            (2 * sector_size + entries, size - sector_size - entries)
+        } else {
+            (sector_size, size)
+        };
+        let unit = libblkid_rs_sys::blkid_loff_t::from(LIBBLKID_SECTOR_SIZE);
+        sectors(div_ceil(start, unit))..sectors(end.max(0) / unit)
+    }
+
+    /// Get the unallocated regions of the usable area of the device. Space
+    /// inside extended partitions is considered allocated.
+    pub fn free_regions(&self) -> Vec<Range<BlkidSectors>> {
+        self.free_regions_within(self.usable_range())
+    }
+
+    /// Get the unallocated regions within `usable`, where the end of `usable`
+    /// is exclusive. The returned regions are in units of 512-byte sectors.
+    pub fn free_regions_within(&self, usable: Range<BlkidSectors>) -> Vec<Range<BlkidSectors>> {
+        let unit = libblkid_rs_sys::blkid_loff_t::from(LIBBLKID_SECTOR_SIZE);
+        let bytes = |s: BlkidSectors| {
+            s.checked_bytes()
+                .map(|b| *b.as_ref())
+                .unwrap_or(libblkid_rs_sys::blkid_loff_t::MAX)
+        };
+        let end = bytes(usable.end) / unit;
+        let mut cursor = div_ceil(bytes(usable.start), unit);
+        let mut regions = Vec::new();
+        for partition in self.partitions.iter().filter(|p| p.parent.is_none()) {
+            let start = *partition.start.as_ref();
+            if start > cursor && cursor < end {
+                regions.push(sectors(cursor)..sectors(start.min(end)));
+            }
+            cursor = cursor.max(*partition.end().as_ref());
+        }
+        if cursor < end {
+            regions.push(sectors(cursor)..sectors(end));
+        }
+        regions
+    }
+
+    /// Get the pairs of partition numbers of all partitions that overlap. A
+    /// logical partition inside its extended partition or a partition of a
+    /// nested partition table inside its parent is not considered an overlap.
+    pub fn overlaps(&self) -> Vec<(libc::c_uint, libc::c_uint)> {
+        let mut overlaps = Vec::new();
+        for (i, a) in self.partitions.iter().enumerate() {
+            for b in &self.partitions[i + 1..] {
+                if b.start >= a.end() {
+                    break;
+                }
+                if b.size.as_ref() != &0
+                    && a.size.as_ref() != &0
+                    && !a.contains_child(b)
+                    && !b.contains_child(a)
+                {
+                    overlaps.push((a.partno, b.partno));
+                }
+            }
+        }
+        overlaps
+    }
+}
+
+/// The role of a partition within its partition table.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionKind {
+    /// A primary partition
+    Primary,
+    /// A DOS extended partition containing logical partitions
+    Extended,
+    /// A logical partition or a partition of a nested partition table
+    Logical,
+}
+
+impl Display for PartitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionKind::Primary => write!(f, "primary"),
+            PartitionKind::Extended => write!(f, "extended"),
+            PartitionKind::Logical => write!(f, "logical"),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TableHeader {
+    pt_type: String,
+    id: Option<String>,
+    offset: BlkidBytes,
+}
+
+impl TableHeader {
+    fn from_table(table: &BlkidParttable<'_>) -> Result<Self> {
+        Ok(TableHeader {
+            pt_type: table.get_type()?,
+            id: table.get_id().ok(),
+            offset: table.get_offset()?,
+        })
+    }
+}
+
+/// A partition in a `PartitionTree`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionNode {
+    /// The partition
+    pub entry: PartitionEntry,
+    /// The role of the partition within its partition table
+    pub kind: PartitionKind,
+    /// The logical partitions of an extended partition sorted by start
+    pub logical: Vec<PartitionNode>,
+    /// A partition table nested inside the partition, such as a BSD disklabel
+    pub nested: Option<PartitionTree>,
+}
+
+/// The layout of a partition table including logical partitions and nested
+/// partition tables.
+///
+/// The `Display` implementation prints the layout as a tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionTree {
+    /// Partition table type such as `dos` or `bsd`
+    pub pt_type: String,
+    /// Partition table ID
+    pub id: Option<String>,
+    /// Offset of the partition table as reported by libblkid; for nested
+    /// tables this is relative to the start of the parent partition
+    pub offset: BlkidBytes,
+    /// The partitions of the table, excluding logical partitions, sorted by
+    /// start
+    pub partitions: Vec<PartitionNode>,
+}
+
+impl PartitionTree {
+    /// Build the tree of partition tables from a list of partitions.
+    pub fn from_partlist(list: &mut BlkidPartlist<'_>) -> Result<Self> {
+        let root = TableHeader::from_table(&list.get_table()?)?;
+        let mut tables = BTreeMap::new();
+        let mut entries = Vec::new();
+        for i in 0..list.number_of_partitions()? {
+            let partition = list.get_partition(i)?;
+            let entry = PartitionEntry::from_partition(&partition)?;
+            if let Some(parent) = entry.parent {
+                if let btree_map::Entry::Vacant(e) = tables.entry(parent) {
+                    e.insert(TableHeader::from_table(&partition.get_table()?)?);
+                }
+            }
+            entries.push(entry);
+        }
+        entries.sort_by(|a, b| a.start.cmp(&b.start).then(a.partno.cmp(&b.partno)));
+        Ok(Self::build(root, None, &entries, &tables))
+    }
+
+    fn build(
+        header: TableHeader,
+        parent: Option<libc::c_uint>,
+        entries: &[PartitionEntry],
+        tables: &BTreeMap<libc::c_uint, TableHeader>,
+    ) -> Self {
+        let node = |entry: &PartitionEntry| PartitionNode {
+            entry: entry.clone(),
+            kind: if entry.is_extended {
+                PartitionKind::Extended
+            } else if entry.is_logical {
+                PartitionKind::Logical
+            } else {
+                PartitionKind::Primary
+            },
+            logical: Vec::new(),
+            nested: tables
+                .get(&entry.partno)
+                .map(|header| Self::build(header.clone(), Some(entry.partno), entries, tables)),
+        };
+        let members = entries
+            .iter()
+            .filter(|entry| entry.parent == parent)
+            .collect::<Vec<_>>();
+        let extended_of = |entry: &PartitionEntry| {
+            members.iter().position(|ext| {
+                ext.is_extended
+                    && entry.is_logical
+                    && entry.start >= ext.start
+                    && entry.end() <= ext.end()
+            })
+        };
+
+        let mut partitions = Vec::new();
+        let mut positions = BTreeMap::new();
+        for (i, entry) in members.iter().enumerate() {
+            if extended_of(entry).is_none() {
+                positions.insert(i, partitions.len());
+                partitions.push(node(entry));
+            }
+        }
+        for entry in &members {
+            if let Some(ext) = extended_of(entry) {
+                partitions[positions[&ext]].logical.push(node(entry));
+            }
+        }
+
+        PartitionTree {
+            pt_type: header.pt_type,
+            id: header.id,
+            offset: header.offset,
+            partitions,
+        }
+    }
+
+    fn fmt_partitions(
+        partitions: &[PartitionNode],
+        prefix: &str,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        for (i, node) in partitions.iter().enumerate() {
+            let (branch, indent) = if i + 1 == partitions.len() {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            let entry = &node.entry;
+            write!(
+                f,
+                "{prefix}{branch}{} {} start {} size {} ",
+                entry.partno,
+                node.kind,
+                entry.start.as_ref(),
+                entry.size.as_ref()
+            )?;
+            match (entry.partition_type, &entry.type_string) {
+                (Some(partition_type), _) => writeln!(f, "{partition_type}")?,
+                (None, Some(type_string)) => writeln!(f, "{type_string}")?,
+                (None, None) => writeln!(f, "{:#04x}", entry.type_code)?,
+            }
+            let child_prefix = format!("{prefix}{indent}");
+            if let Some(ref nested) = node.nested {
+                write!(f, "{child_prefix}└─ ")?;
+                nested.fmt_tree(&format!("{child_prefix}   "), f)?;
+            }
+            Self::fmt_partitions(&node.logical, &child_prefix, f)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_tree(&self, prefix: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.id {
+            Some(ref id) => writeln!(f, "{} {}", self.pt_type, id)?,
+            None => writeln!(f, "{}", self.pt_type)?,
+        }
+        Self::fmt_partitions(&self.partitions, prefix, f)
+    }
+}
+
+impl Display for PartitionTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree("", f)
+    }
+}
+
+impl BlkidProbe {
+    /// Get an owned snapshot of the partition table found by the last probing
+    /// operation.
+    pub fn get_partition_table(&mut self) -> Result<PartitionTable> {
+        PartitionTable::from_probe(self)
+    }
+
+    /// Get the tree of partition tables found by the last probing operation.
+    pub fn get_partition_tree(&mut self) -> Result<PartitionTree> {
+        PartitionTree::from_partlist(&mut self.get_partitions()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::attributes::DosFlags;
+
+    fn entry(partno: libc::c_uint, start: i64, size: i64) -> PartitionEntry {
+        PartitionEntry {
+            partno,
+            start: sectors(start),
+            size: sectors(size),
+            uuid: None,
+            name: None,
+            type_code: 0x83,
+            type_string: None,
+            partition_type: Some(PartitionType::LinuxFilesystem),
+            flags: PartitionFlags::Dos(DosFlags::new(0)),
+            is_extended: false,
+            is_logical: false,
+            is_primary: true,
+            parent: None,
+        }
+    }
+
+    fn table(partitions: Vec<PartitionEntry>) -> PartitionTable {
+        PartitionTable {
+            pt_type: "dos".to_string(),
+            id: None,
+            offset: BlkidBytes::new(0),
+            device_size: BlkidBytes::new(100 * 1024 * 1024),
+            sector_size: 512,
+            partitions,
+        }
+    }
+
+    #[test]
+    fn test_free_regions() {
+        let table = table(vec![entry(1, 2048, 2048), entry(2, 8192, 2048)]);
+        assert_eq!(
+            table.free_regions(),
+            vec![
+                sectors(1)..sectors(2048),
+                sectors(4096)..sectors(8192),
+                sectors(10240)..sectors(204800),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let mut extended = entry(2, 4096, 8192);
+        extended.is_extended = true;
+        extended.is_primary = false;
+        let mut logical = entry(5, 6144, 2048);
+        logical.is_logical = true;
+        logical.is_primary = false;
+        let table = table(vec![
+            entry(1, 2048, 4096),
+            extended,
+            logical,
+            entry(3, 20480, 2048),
+        ]);
+        assert_eq!(table.overlaps(), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_tree() {
+        let mut extended = entry(2, 8192, 16384);
+        extended.is_extended = true;
+        extended.is_primary = false;
+        extended.type_code = 0x05;
+        extended.partition_type = Some(PartitionType::Extended);
+        let mut logical = entry(5, 10240, 4096);
+        logical.is_logical = true;
+        logical.is_primary = false;
+        let mut bsd = entry(7, 32784, 4096);
+        bsd.is_logical = true;
+        bsd.is_primary = false;
+        bsd.parent = Some(3);
+        bsd.partition_type = None;
+        let mut bsd_swap = bsd.clone();
+        bsd_swap.partno = 8;
+        bsd_swap.start = sectors(36880);
+        bsd_swap.size = sectors(4096);
+        bsd_swap.type_code = 0x01;
+        let entries = vec![
+            entry(1, 2048, 4096),
+            extended,
+            logical,
+            entry(3, 32768, 8192),
+            bsd,
+            bsd_swap,
+        ];
+        let header = |pt_type: &str| TableHeader {
+            pt_type: pt_type.to_string(),
+            id: None,
+            offset: BlkidBytes::new(0),
+        };
+        let tables = BTreeMap::from([(3, header("bsd"))]);
+        let tree = PartitionTree::build(header("dos"), None, &entries, &tables);
+        assert_eq!(tree.partitions.len(), 3);
+        assert_eq!(tree.partitions[1].kind, PartitionKind::Extended);
+        assert_eq!(tree.partitions[1].logical[0].entry.partno, 5);
+        let nested = tree.partitions[2].nested.as_ref().unwrap();
+        assert_eq!(nested.pt_type, "bsd");
+        assert_eq!(nested.partitions[0].entry.partno, 7);
+        let expected = [
+            "dos",
+            "├─ 1 primary start 2048 size 4096 Linux filesystem",
+            "├─ 2 extended start 8192 size 16384 Extended",
+            "│  └─ 5 logical start 10240 size 4096 Linux filesystem",
+            "└─ 3 primary start 32768 size 8192 Linux filesystem",
+            "   └─ bsd",
+            "      ├─ 7 logical start 32784 size 4096 0x83",
+            "      └─ 8 logical start 36880 size 4096 0x01",
+            "",
+        ];
+        assert_eq!(tree.to_string(), expected.join("\n"));
+    }
+}
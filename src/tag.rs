@@ -12,18 +12,23 @@ use libc::c_char;
 use crate::{Result, err::BlkidErr};
 
 /// Iterator for tags associated with a device
+///
+/// Tags with a type or value that is not valid UTF8 are skipped. Use
+/// `BlkidTagIter::bytes` to iterate over all tags.
 pub struct BlkidTagIter(libblkid_rs_sys::blkid_tag_iterate);
 
 impl BlkidTagIter {
     pub(crate) fn new(iter: libblkid_rs_sys::blkid_tag_iterate) -> Self {
         BlkidTagIter(iter)
     }
-}
 
-impl Iterator for BlkidTagIter {
-    type Item = (String, String);
+    /// Convert this iterator into an iterator that yields tags and values as
+    /// bytes so that values that are not valid UTF8 can be accessed.
+    pub fn bytes(self) -> BlkidTagBytesIter {
+        BlkidTagBytesIter(self)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_cstrs(&mut self) -> Option<(&CStr, &CStr)> {
         let mut type_: *const c_char = ptr::null_mut();
         let mut value: *const c_char = ptr::null_mut();
         if unsafe {
@@ -37,12 +42,20 @@ impl Iterator for BlkidTagIter {
             None
         } else {
             assert!(!type_.is_null() && !value.is_null());
-            let type_str = unsafe { CStr::from_ptr(type_) };
-            let value_str = unsafe { CStr::from_ptr(value) };
-            Some((
-                type_str.to_str().ok()?.to_string(),
-                value_str.to_str().ok()?.to_string(),
-            ))
+            Some(unsafe { (CStr::from_ptr(type_), CStr::from_ptr(value)) })
+        }
+    }
+}
+
+impl Iterator for BlkidTagIter {
+    type Item = (String, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (type_str, value_str) = self.next_cstrs()?;
+            if let (Ok(type_str), Ok(value_str)) = (type_str.to_str(), value_str.to_str()) {
+                return Some((type_str.to_string(), value_str.to_string()));
+            }
         }
     }
 }
@@ -53,6 +66,20 @@ impl Drop for BlkidTagIter {
     }
 }
 
+/// Iterator for tags associated with a device that yields tags and values as
+/// bytes without the terminating null byte
+pub struct BlkidTagBytesIter(BlkidTagIter);
+
+impl Iterator for BlkidTagBytesIter {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_cstrs().map(|(type_str, value_str)| {
+            (type_str.to_bytes().to_vec(), value_str.to_bytes().to_vec())
+        })
+    }
+}
+
 /// Parse a tag string into a tuple of type and value
 pub fn parse_tag_string(tag_string: &str) -> Result<(String, String)> {
     let tag_cstring = CString::new(tag_string)?;
@@ -0,0 +1,75 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers shared by unit tests.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A scratch directory that is removed when dropped, even if the test
+/// panics.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty directory unique to `name` and this process.
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("libblkid-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    /// Get the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Size of the test images. libblkid never reports images the size of a
+/// floppy disk as ambiguous so the images are larger than that.
+pub const IMAGE_SIZE: usize = 4 << 20;
+
+/// Get an image with an ext2 magic at 0x438.
+pub fn ext2_image() -> Vec<u8> {
+    let mut data = vec![0u8; IMAGE_SIZE];
+    data[0x438..0x43a].copy_from_slice(&[0x53, 0xef]);
+    data
+}
+
+/// Get an image with an ext2 magic at 0x438 and a swap magic at the end of
+/// the first 4 KiB page. The version 1 swap header at 0x400 shares its
+/// location with the ext2 superblock. libblkid reports the image as
+/// ambiguous.
+pub fn ext2_swap_image() -> Vec<u8> {
+    let mut data = ext2_image();
+    data[0x400] = 1;
+    data[0x404] = 0xff;
+    data[0xff6..0x1000].copy_from_slice(b"SWAPSPACE2");
+    data
+}
+
+/// Get an image with a DOS partition table with the disk identifier
+/// `0x12345678` and primary partitions given by type, start sector and size
+/// in sectors.
+pub fn dos_image(partitions: &[(u8, u32, u32)]) -> Vec<u8> {
+    let mut data = vec![0u8; IMAGE_SIZE];
+    data[0x1b8..0x1bc].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    for (i, &(part_type, start, size)) in partitions.iter().enumerate() {
+        let entry = 0x1be + i * 16;
+        data[entry + 4] = part_type;
+        data[entry + 8..entry + 12].copy_from_slice(&start.to_le_bytes());
+        data[entry + 12..entry + 16].copy_from_slice(&size.to_le_bytes());
+    }
+    data[0x1fe..0x200].copy_from_slice(&[0x55, 0xaa]);
+    data
+}
@@ -2,6 +2,9 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+#[cfg(any(blkid236supported, blkid239supported))]
+use crate::{Result, version::check_library_version};
+
 /// A handle for traversing the blkid topology of devices.
 pub struct BlkidTopology(libblkid_rs_sys::blkid_topology);
 
@@ -34,4 +37,70 @@ impl BlkidTopology {
     pub fn get_physical_sector_size(&self) -> libc::c_ulong {
         unsafe { libblkid_rs_sys::blkid_topology_get_physical_sector_size(self.0) }
     }
+
+    /// Check whether the device supports direct access (DAX).
+    ///
+    /// Requires libblkid 2.36 or later.
+    #[cfg(blkid236supported)]
+    pub fn get_dax(&self) -> Result<bool> {
+        check_library_version(2, 36)?;
+        Ok(unsafe { libblkid_rs_sys::blkid_topology_get_dax(self.0) } != 0)
+    }
+
+    /// Get the disk sequence number of the device. The sequence number is
+    /// incremented each time a new medium is attached to the device so it
+    /// distinguishes reuses of loop and nbd devices. A value of `0` means the
+    /// sequence number is not available.
+    ///
+    /// Requires libblkid 2.39 or later.
+    #[cfg(blkid239supported)]
+    pub fn get_diskseq(&self) -> Result<u64> {
+        check_library_version(2, 39)?;
+        Ok(unsafe { libblkid_rs_sys::blkid_topology_get_diskseq(self.0) })
+    }
+
+    /// Get an owned snapshot of all topology values that remains valid after
+    /// further probing.
+    pub fn info(&self) -> TopologyInfo {
+        #[cfg(blkid236supported)]
+        let dax = self.get_dax().ok();
+        #[cfg(not(blkid236supported))]
+        let dax = None;
+
+        #[cfg(blkid239supported)]
+        let diskseq = self.get_diskseq().ok().filter(|diskseq| *diskseq != 0);
+        #[cfg(not(blkid239supported))]
+        let diskseq = None;
+
+        TopologyInfo {
+            alignment_offset: self.get_alignment_offset(),
+            minimum_io_size: self.get_minimum_io_size(),
+            optimal_io_size: self.get_optimal_io_size(),
+            logical_sector_size: self.get_logical_sector_size(),
+            physical_sector_size: self.get_physical_sector_size(),
+            dax,
+            diskseq,
+        }
+    }
+}
+
+/// An owned snapshot of the topology of a device.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TopologyInfo {
+    /// Alignment offset in bytes
+    pub alignment_offset: libc::c_ulong,
+    /// Minimum size of an IO operation in bytes
+    pub minimum_io_size: libc::c_ulong,
+    /// Optimal size of an IO operation in bytes
+    pub optimal_io_size: libc::c_ulong,
+    /// Size of a logical sector in bytes
+    pub logical_sector_size: libc::c_ulong,
+    /// Size of a physical sector in bytes
+    pub physical_sector_size: libc::c_ulong,
+    /// Whether the device supports direct access (DAX) or `None` if the
+    /// version of libblkid does not report it
+    pub dax: Option<bool>,
+    /// Disk sequence number or `None` if it is not available
+    pub diskseq: Option<u64>,
 }
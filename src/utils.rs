@@ -3,7 +3,10 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    cmp::Ordering,
     ffi::{CStr, CString},
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
     ptr,
 };
@@ -12,19 +15,102 @@ use either::Either;
 
 use crate::{Result, cache::BlkidCache, err::BlkidErr};
 
-const SECTOR_SIZE: libblkid_rs_sys::blkid_loff_t = 512;
+// libblkid reports partition offsets and sizes and the sector count of a
+// probe in 512-byte sectors regardless of the sector size of the device.
+pub(crate) const LIBBLKID_SECTOR_SIZE: libc::c_uint = 512;
 
-/// A struct representing a count with units of sectors.
-pub struct BlkidSectors(libblkid_rs_sys::blkid_loff_t);
+/// A struct representing a count with units of sectors of a given size.
+///
+/// The sector size of a device is available from
+/// `BlkidProbe::get_sector_size` or `BlkidTopology::get_logical_sector_size`.
+/// Partition offsets and sizes are always reported by libblkid in 512-byte
+/// sectors.
+///
+/// Counts are compared by the number of bytes they represent so counts with
+/// different sector sizes can be compared. Arithmetic is only provided as
+/// checked methods because counts with different sector sizes cannot be added
+/// or subtracted without converting one of them.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlkidSectors(libblkid_rs_sys::blkid_loff_t, libc::c_uint);
 
 impl BlkidSectors {
-    pub(crate) fn new(num: libblkid_rs_sys::blkid_loff_t) -> Self {
-        BlkidSectors(num)
+    /// Create a count of `num` sectors each `sector_size` bytes in size.
+    pub fn new(num: libblkid_rs_sys::blkid_loff_t, sector_size: libc::c_uint) -> Self {
+        BlkidSectors(num, sector_size)
+    }
+
+    /// Return the size in bytes of the sectors counted.
+    pub fn sector_size(&self) -> libc::c_uint {
+        self.1
     }
 
     /// Return the number of bytes represented by this number of disk sectors.
+    /// The result saturates if it does not fit; use `checked_bytes` to detect
+    /// this.
     pub fn bytes(&self) -> BlkidBytes {
-        BlkidBytes(self.0 * SECTOR_SIZE)
+        BlkidBytes(
+            self.0
+                .saturating_mul(libblkid_rs_sys::blkid_loff_t::from(self.1)),
+        )
+    }
+
+    /// Return the number of bytes represented by this number of disk sectors
+    /// or `None` if the result overflows.
+    pub fn checked_bytes(&self) -> Option<BlkidBytes> {
+        self.0
+            .checked_mul(libblkid_rs_sys::blkid_loff_t::from(self.1))
+            .map(BlkidBytes)
+    }
+
+    /// Return the same number of bytes as a count of sectors of size
+    /// `sector_size` or an error if the number of bytes is not divisible by
+    /// `sector_size`.
+    pub fn with_sector_size(&self, sector_size: libc::c_uint) -> Result<BlkidSectors> {
+        self.checked_bytes()
+            .ok_or(BlkidErr::InvalidConv)?
+            .sectors_of_size(sector_size)
+    }
+
+    /// Add two counts with the same sector size, returning `None` if the
+    /// sector sizes differ or the result overflows.
+    pub fn checked_add(&self, other: BlkidSectors) -> Option<BlkidSectors> {
+        if self.1 != other.1 {
+            return None;
+        }
+        self.0
+            .checked_add(other.0)
+            .map(|num| BlkidSectors(num, self.1))
+    }
+
+    /// Subtract two counts with the same sector size, returning `None` if the
+    /// sector sizes differ or the result overflows.
+    pub fn checked_sub(&self, other: BlkidSectors) -> Option<BlkidSectors> {
+        if self.1 != other.1 {
+            return None;
+        }
+        self.0
+            .checked_sub(other.0)
+            .map(|num| BlkidSectors(num, self.1))
+    }
+
+    /// Multiply the count by `factor`, returning `None` if the result
+    /// overflows.
+    pub fn checked_mul(&self, factor: libblkid_rs_sys::blkid_loff_t) -> Option<BlkidSectors> {
+        self.0
+            .checked_mul(factor)
+            .map(|num| BlkidSectors(num, self.1))
+    }
+
+    /// Divide the count by `divisor`, returning `None` if `divisor` is zero.
+    pub fn checked_div(&self, divisor: libblkid_rs_sys::blkid_loff_t) -> Option<BlkidSectors> {
+        self.0
+            .checked_div(divisor)
+            .map(|num| BlkidSectors(num, self.1))
+    }
+
+    fn cmp_bytes(&self) -> i128 {
+        i128::from(self.0) * i128::from(self.1)
     }
 }
 
@@ -34,23 +120,103 @@ impl AsRef<libblkid_rs_sys::blkid_loff_t> for BlkidSectors {
     }
 }
 
+impl PartialEq for BlkidSectors {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp_bytes() == other.cmp_bytes()
+    }
+}
+
+impl Eq for BlkidSectors {}
+
+impl PartialOrd for BlkidSectors {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BlkidSectors {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_bytes().cmp(&other.cmp_bytes())
+    }
+}
+
+impl Hash for BlkidSectors {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cmp_bytes().hash(state)
+    }
+}
+
+impl Display for BlkidSectors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.checked_bytes() {
+            Some(bytes) => write!(f, "{} {}-byte sectors ({})", self.0, self.1, bytes),
+            None => write!(f, "{} {}-byte sectors", self.0, self.1),
+        }
+    }
+}
+
 /// A struct repsenting a count with units of bytes.
+///
+/// Byte counts are displayed using binary units, for example `1.50 GiB`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlkidBytes(libblkid_rs_sys::blkid_loff_t);
 
 impl BlkidBytes {
-    pub(crate) fn new(num: libblkid_rs_sys::blkid_loff_t) -> Self {
+    /// Create a count of `num` bytes.
+    pub fn new(num: libblkid_rs_sys::blkid_loff_t) -> Self {
         BlkidBytes(num)
     }
 
-    /// Return the number of sectors represented by this number of bytes or
-    /// an error if the number of bytes is not divisible by the sector size.
+    /// Return the number of 512-byte sectors represented by this number of
+    /// bytes or an error if the number of bytes is not divisible by 512.
     pub fn sectors(&self) -> Result<BlkidSectors> {
-        if self.0 % SECTOR_SIZE != 0 {
+        self.sectors_of_size(LIBBLKID_SECTOR_SIZE)
+    }
+
+    /// Return the number of sectors of size `sector_size` represented by this
+    /// number of bytes or an error if the number of bytes is not divisible by
+    /// `sector_size`.
+    pub fn sectors_of_size(&self, sector_size: libc::c_uint) -> Result<BlkidSectors> {
+        let size = libblkid_rs_sys::blkid_loff_t::from(sector_size);
+        if size == 0 || self.0 % size != 0 {
+            Err(BlkidErr::InvalidConv)
+        } else {
+            Ok(BlkidSectors(self.0 / size, sector_size))
+        }
+    }
+
+    /// Return the number of whole sectors of size `sector_size` that fit in
+    /// this number of bytes, discarding any remainder.
+    pub fn sectors_of_size_floor(&self, sector_size: libc::c_uint) -> Result<BlkidSectors> {
+        let size = libblkid_rs_sys::blkid_loff_t::from(sector_size);
+        if size == 0 {
             Err(BlkidErr::InvalidConv)
         } else {
-            Ok(BlkidSectors(self.0 / SECTOR_SIZE))
+            Ok(BlkidSectors(self.0.div_euclid(size), sector_size))
         }
     }
+
+    /// Add two byte counts, returning `None` if the result overflows.
+    pub fn checked_add(&self, other: BlkidBytes) -> Option<BlkidBytes> {
+        self.0.checked_add(other.0).map(BlkidBytes)
+    }
+
+    /// Subtract two byte counts, returning `None` if the result overflows.
+    pub fn checked_sub(&self, other: BlkidBytes) -> Option<BlkidBytes> {
+        self.0.checked_sub(other.0).map(BlkidBytes)
+    }
+
+    /// Multiply the count by `factor`, returning `None` if the result
+    /// overflows.
+    pub fn checked_mul(&self, factor: libblkid_rs_sys::blkid_loff_t) -> Option<BlkidBytes> {
+        self.0.checked_mul(factor).map(BlkidBytes)
+    }
+
+    /// Divide the count by `divisor`, returning `None` if `divisor` is zero.
+    pub fn checked_div(&self, divisor: libblkid_rs_sys::blkid_loff_t) -> Option<BlkidBytes> {
+        self.0.checked_div(divisor).map(BlkidBytes)
+    }
 }
 
 impl AsRef<libblkid_rs_sys::blkid_loff_t> for BlkidBytes {
@@ -59,6 +225,23 @@ impl AsRef<libblkid_rs_sys::blkid_loff_t> for BlkidBytes {
     }
 }
 
+impl Display for BlkidBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        if self.0.unsigned_abs() < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.2} {}", value, UNITS[unit])
+    }
+}
+
 /// Send a uevent to a device specified by the device path
 pub fn send_uevent(dev: &Path, action: &str) -> Result<()> {
     let dev_cstring = CString::new(dev.display().to_string())?;
@@ -111,3 +294,45 @@ pub fn evaluate_tag(token: &str, value: &str, cache: Option<&mut BlkidCache>) ->
 pub fn evaluate_spec(tag_or_dm_path: &str, cache: Option<&mut BlkidCache>) -> Result<PathBuf> {
     evaluate(Either::Right(tag_or_dm_path), cache)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sector_conversions() {
+        let sectors = BlkidSectors::new(2048, 512);
+        assert_eq!(*sectors.bytes().as_ref(), 1024 * 1024);
+        let native = sectors.with_sector_size(4096).unwrap();
+        assert_eq!(*native.as_ref(), 256);
+        assert_eq!(native, sectors);
+        assert!(BlkidSectors::new(1, 512).with_sector_size(4096).is_err());
+        assert_eq!(
+            *BlkidBytes::new(5000)
+                .sectors_of_size_floor(4096)
+                .unwrap()
+                .as_ref(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_sector_arithmetic() {
+        let a = BlkidSectors::new(10, 4096);
+        let b = BlkidSectors::new(4, 4096);
+        assert_eq!(*a.checked_sub(b).unwrap().as_ref(), 6);
+        assert!(BlkidSectors::new(8, 512) < b);
+        assert!(a.checked_add(BlkidSectors::new(1, 512)).is_none());
+        assert_eq!(a.checked_mul(2), Some(BlkidSectors::new(20, 4096)));
+        assert!(a.checked_mul(libblkid_rs_sys::blkid_loff_t::MAX).is_none());
+        assert!(a.checked_div(0).is_none());
+        assert!(BlkidBytes::new(1).checked_div(0).is_none());
+        assert_eq!(
+            BlkidSectors::new(libblkid_rs_sys::blkid_loff_t::MAX, 512).bytes(),
+            BlkidBytes::new(libblkid_rs_sys::blkid_loff_t::MAX)
+        );
+        assert_eq!(BlkidBytes::new(1536).to_string(), "1.50 KiB");
+        assert_eq!(BlkidBytes::new(100).to_string(), "100 B");
+        assert_eq!(a.to_string(), "10 4096-byte sectors (40.00 KiB)");
+    }
+}
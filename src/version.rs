@@ -10,6 +10,8 @@ use std::{
 use libc::{c_char, c_int};
 
 use crate::Result;
+#[cfg(blkid236supported)]
+use crate::err::BlkidErr;
 
 /// Parse a version string into a version code
 pub fn parse_version_string(version_str: &str) -> Result<c_int> {
@@ -31,3 +33,35 @@ pub fn get_library_version() -> Result<(c_int, String, String)> {
     let date_string = unsafe { CStr::from_ptr(date_ptr) }.to_str()?.to_string();
     Ok((ver_code, ver_string, date_string))
 }
+
+// Return an error if the libblkid library in use is older than the given
+// version. A version code consists of the digits of the version string so the
+// digits of the patch level are dropped from the code of the library before
+// the codes are compared.
+#[cfg(blkid236supported)]
+pub(crate) fn check_library_version(major: u32, minor: u32) -> Result<()> {
+    let required = parse_version_string(&format!("{major}.{minor}"))?;
+    let (mut code, ver_string, _) = get_library_version()?;
+    while code.checked_ilog10() > required.checked_ilog10() {
+        code /= 10;
+    }
+    if code >= required {
+        Ok(())
+    } else {
+        Err(BlkidErr::Other(format!(
+            "This operation requires libblkid {major}.{minor} or later; found version {ver_string}"
+        )))
+    }
+}
+
+#[cfg(all(test, blkid236supported))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_library_version() {
+        assert!(check_library_version(2, 36).is_ok());
+        assert!(check_library_version(2, 99).is_err());
+        assert!(check_library_version(3, 0).is_err());
+    }
+}